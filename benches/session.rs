@@ -1,12 +1,15 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use muesli::session_decode;
+use muesli::{session_decode, validate_session};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     let session = include_bytes!("data/test.session");
     c.bench_function("decode large session", |b| {
         b.iter(|| session_decode(session));
     });
+    c.bench_function("validate large session", |b| {
+        b.iter(|| validate_session(session));
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
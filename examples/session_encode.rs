@@ -3,16 +3,16 @@ use muesli::{session_encode, SessionEntry, Value};
 fn main() {
     let data = vec![
         SessionEntry {
-            key: b"foo",
-            value: Value::Integer(42),
+            key: b"foo".into(),
+            value: Some(Value::Integer(42)),
         },
         SessionEntry {
-            key: b"bar",
-            value: Value::String(b"baz|qux".as_slice()),
+            key: b"bar".into(),
+            value: Some(Value::String(b"baz|qux".into())),
         },
         SessionEntry {
-            key: b"pub",
-            value: Value::Integer(1337),
+            key: b"pub".into(),
+            value: Some(Value::Integer(1337)),
         },
     ];
 
@@ -1,50 +1,305 @@
+pub(crate) mod lazy;
 mod raw;
+#[cfg(feature = "serde")]
+pub(crate) mod serde;
+pub(crate) mod stream;
+pub(crate) mod validate;
+
+use std::{borrow::Cow, fmt};
 
 use winnow::{
     binary::{length_repeat, length_take},
-    combinator::{alt, delimited, empty, repeat, separated_pair, terminated},
-    error::{ContextError, ParseError, StrContext},
+    combinator::{
+        alt, delimited, dispatch, empty, eof, fail, opt, peek, repeat_till, separated_pair,
+        terminated,
+    },
+    error::{ContextError, FromExternalError, StrContext},
     seq,
-    token::{one_of, rest, take_until},
+    stream::{Accumulate, Stateful, Stream},
+    token::{any, one_of, rest, take, take_until},
     Parser, Result,
 };
 
-use crate::value::{ArrayKey, ObjectProperty, ObjectPropertyVisibility, SessionEntry, Value};
-
-fn any_value<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
-    alt((
-        value_null,
-        value_boolean,
-        value_integer,
-        value_decimal,
-        value_string,
-        value_array,
-        value_object,
-        value_custom_object,
-        value_reference_to_value,
-        value_reference_to_object,
-    ))
+use crate::{
+    error::{Error, LimitExceeded},
+    value::{
+        canonical_integer, ArrayKey, NumericKeys, ObjectProperty, ObjectPropertyVisibility,
+        SessionEntry, SessionFormat, Value,
+    },
+};
+
+/// Options tuning how input is decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeOptions {
+    session_format: SessionFormat,
+    max_depth: usize,
+    max_container_len: usize,
+    max_total_elements: usize,
+    max_string_len: usize,
+    numeric_keys: NumericKeys,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            session_format: SessionFormat::default(),
+            max_depth: 128,
+            max_container_len: usize::MAX,
+            max_total_elements: usize::MAX,
+            max_string_len: usize::MAX,
+            numeric_keys: NumericKeys::default(),
+        }
+    }
+}
+
+impl DecodeOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Format expected by `session_decode_with`.
+    #[must_use]
+    pub fn with_session_format(mut self, session_format: SessionFormat) -> Self {
+        self.session_format = session_format;
+        self
+    }
+
+    /// Maximum nesting depth of arrays and objects, like PHP's `unserialize_max_depth`.
+    ///
+    /// Defaults to 128. Decoding is recursive, so raising it toward PHP's default of 4096 requires
+    /// running the decoder on a thread with a larger stack.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Maximum number of elements an array or object may declare.
+    ///
+    /// Unlimited by default. Declared sizes are always checked against the remaining input.
+    #[must_use]
+    pub fn with_max_container_len(mut self, max_container_len: usize) -> Self {
+        self.max_container_len = max_container_len;
+        self
+    }
+
+    /// Maximum number of elements declared by all arrays and objects of the input together.
+    ///
+    /// Unlimited by default.
+    #[must_use]
+    pub fn with_max_total_elements(mut self, max_total_elements: usize) -> Self {
+        self.max_total_elements = max_total_elements;
+        self
+    }
+
+    /// Maximum length a string may declare, class names and property names included.
+    ///
+    /// Unlimited by default. Declared lengths are always checked against the remaining input.
+    #[must_use]
+    pub fn with_max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = max_string_len;
+        self
+    }
+
+    /// How string array keys holding an integer, like `s:2:"42";`, are read.
+    ///
+    /// Kept as strings by default.
+    #[must_use]
+    pub fn with_numeric_keys(mut self, numeric_keys: NumericKeys) -> Self {
+        self.numeric_keys = numeric_keys;
+        self
+    }
+}
+
+/// Reason of a failure that parsers attach to their error, turned into an [`Error`] once decoding
+/// stops.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Cause {
+    DepthExceeded { max: usize },
+    LengthMismatch { declared: usize },
+    Limit(LimitExceeded),
+    NumericStringKey(i64),
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cause::DepthExceeded { max } => write!(f, "maximum depth of {max} exceeded"),
+            Cause::LengthMismatch { declared } => {
+                write!(
+                    f,
+                    "string doesn't end after its declared length of {declared}"
+                )
+            }
+            Cause::Limit(limit) => limit.fmt(f),
+            Cause::NumericStringKey(key) => {
+                write!(f, "string array key \"{key}\" should be the integer {key}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Cause {}
+
+#[derive(Debug)]
+pub(crate) struct State {
+    options: DecodeOptions,
+    depth: usize,
+    elements: usize,
+}
+
+impl State {
+    pub(crate) fn new(options: DecodeOptions) -> Self {
+        Self {
+            options,
+            depth: 0,
+            elements: 0,
+        }
+    }
+}
+
+pub(crate) type Input<'s> = Stateful<&'s [u8], State>;
+
+/// Fail at the current position, attaching `cause` to the error.
+pub(crate) fn fail_with<O>(input: &Input<'_>, cause: Cause) -> Result<O> {
+    Err(ContextError::from_external_error(input, cause))
+}
+
+/// Run `parser` one nesting level deeper, failing once the maximum depth is reached.
+fn nested<'s, O>(
+    mut parser: impl Parser<Input<'s>, O, ContextError>,
+) -> impl Parser<Input<'s>, O, ContextError> {
+    move |input: &mut Input<'s>| {
+        let max = input.state.options.max_depth;
+        if input.state.depth >= max {
+            return fail_with(input, Cause::DepthExceeded { max });
+        }
+        input.state.depth += 1;
+        let output = parser.parse_next(input);
+        input.state.depth -= 1;
+        output
+    }
+}
+
+/// Number of elements declared by an array or object, each taking at least `min_element_len`
+/// bytes of input.
+fn container_size<'s>(min_element_len: usize) -> impl Parser<Input<'s>, usize, ContextError> {
+    move |input: &mut Input<'s>| {
+        let checkpoint = input.checkpoint();
+        let declared = raw::size.parse_next(input)?;
+        let options = &input.state.options;
+        if declared > options.max_container_len {
+            let max = options.max_container_len;
+            input.reset(&checkpoint);
+            return fail_with(
+                input,
+                Cause::Limit(LimitExceeded::ContainerLength { declared, max }),
+            );
+        }
+        let remaining = input.eof_offset();
+        if declared.saturating_mul(min_element_len) > remaining {
+            input.reset(&checkpoint);
+            return fail_with(
+                input,
+                Cause::Limit(LimitExceeded::RemainingInput {
+                    declared,
+                    remaining,
+                }),
+            );
+        }
+        let elements = input.state.elements.saturating_add(declared);
+        if elements > input.state.options.max_total_elements {
+            let max = input.state.options.max_total_elements;
+            input.reset(&checkpoint);
+            return fail_with(input, Cause::Limit(LimitExceeded::TotalElements { max }));
+        }
+        input.state.elements = elements;
+        Ok(declared)
+    }
+}
+
+/// Run `parser` over the whole `input`.
+fn decode<'s, O>(
+    input: &'s [u8],
+    options: &DecodeOptions,
+    mut parser: impl Parser<Input<'s>, O, ContextError>,
+) -> Result<O, Error> {
+    let mut stream = Input {
+        input,
+        state: State::new(options.clone()),
+    };
+    let output = parser.parse_next(&mut stream);
+    let offset = input.len() - stream.input.len();
+    match output {
+        Ok(_) if !stream.input.is_empty() => Err(Error::TrailingData { offset }),
+        Ok(output) => Ok(output),
+        Err(err) => Err(decode_error(&err, offset, stream.input.is_empty())),
+    }
+}
+
+fn decode_error(err: &ContextError, offset: usize, eof: bool) -> Error {
+    match err.cause().and_then(|cause| cause.downcast_ref::<Cause>()) {
+        Some(Cause::DepthExceeded { max }) => Error::DepthExceeded { offset, max: *max },
+        Some(Cause::LengthMismatch { declared }) => Error::LengthMismatch {
+            offset,
+            declared: *declared,
+        },
+        Some(Cause::Limit(limit)) => Error::LimitExceeded {
+            offset,
+            limit: *limit,
+        },
+        Some(Cause::NumericStringKey(key)) => Error::NumericStringKey { offset, key: *key },
+        None => {
+            let expected = err.context().find_map(|context| match context {
+                StrContext::Label(label) => Some(*label),
+                _ => None,
+            });
+            if eof {
+                Error::UnexpectedEof { offset, expected }
+            } else {
+                Error::InvalidToken { offset, expected }
+            }
+        }
+    }
+}
+
+fn any_value<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
+    dispatch! {peek(any);
+        b'N' => value_null,
+        b'b' => value_boolean,
+        b'i' => value_integer,
+        b'd' => value_decimal,
+        b's' | b'S' => value_string,
+        b'a' => value_array,
+        b'O' => value_object,
+        b'C' => value_custom_object,
+        b'E' => value_enum,
+        b'R' => value_reference_to_value,
+        b'r' => value_reference_to_object,
+        _ => fail.context(StrContext::Label("value type")),
+    }
     .parse_next(input)
 }
 
-fn value_null<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
+fn value_null<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
     b"N;".value(Value::Null).parse_next(input)
 }
 
-fn value_boolean<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
+fn value_boolean<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
     delimited(b"b:", one_of(b"01"), b';')
         .map(|bool| Value::Boolean(bool == b'1'))
         .parse_next(input)
 }
 
-fn value_integer<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
+fn value_integer<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
     delimited(b"i:", raw::signed_integer, b';')
         .parse_to()
         .map(Value::Integer)
         .parse_next(input)
 }
 
-fn value_decimal<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
+fn value_decimal<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
     delimited(
         b"d:",
         alt((
@@ -59,70 +314,112 @@ fn value_decimal<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
     .parse_next(input)
 }
 
-fn value_string<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
-    delimited(b"s:", raw::sized_string, b';')
-        .map(Value::String)
-        .parse_next(input)
+fn string<'s>(input: &mut Input<'s>) -> Result<Cow<'s, [u8]>> {
+    dispatch! {peek(any);
+        b's' => delimited(b"s:", raw::sized_string, b';').map(Cow::Borrowed),
+        b'S' => delimited(b"S:", raw::escaped_string, b';').map(Cow::Owned),
+        _ => fail.context(StrContext::Label("string")),
+    }
+    .parse_next(input)
 }
 
-fn array_key<'s>(input: &mut &'s [u8]) -> Result<ArrayKey<'s>> {
-    alt((
-        delimited(b"i:", raw::signed_integer, b';')
+fn value_string<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
+    string.map(Value::String).parse_next(input)
+}
+
+fn array_key<'s>(input: &mut Input<'s>) -> Result<ArrayKey<'s>> {
+    dispatch! {peek(any);
+        b'i' => delimited(b"i:", raw::signed_integer, b';')
             .parse_to()
             .map(ArrayKey::Integer),
-        delimited(b"s:", raw::sized_string, b';').map(ArrayKey::String),
-    ))
+        b's' | b'S' => string_key,
+        _ => fail.context(StrContext::Label("array key")),
+    }
     .parse_next(input)
 }
 
-fn array_pair<'s>(input: &mut &'s [u8]) -> Result<(ArrayKey<'s>, Value<'s>)> {
-    (array_key, any_value).parse_next(input)
+/// String array key, read as set by [`DecodeOptions::with_numeric_keys`].
+fn string_key<'s>(input: &mut Input<'s>) -> Result<ArrayKey<'s>> {
+    let checkpoint = input.checkpoint();
+    let key = string.parse_next(input)?;
+    let numeric_keys = input.state.options.numeric_keys;
+    if numeric_keys == NumericKeys::Keep {
+        return Ok(ArrayKey::String(key));
+    }
+    match canonical_integer(&key) {
+        Some(n) if numeric_keys == NumericKeys::Canonicalize => Ok(ArrayKey::Integer(n)),
+        Some(n) => {
+            input.reset(&checkpoint);
+            fail_with(input, Cause::NumericStringKey(n))
+        }
+        None => Ok(ArrayKey::String(key)),
+    }
 }
 
-fn value_array<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
+/// Array whose values are read by `value`.
+fn array<'s, V, A: Accumulate<(ArrayKey<'s>, V)>>(
+    value: impl Parser<Input<'s>, V, ContextError>,
+) -> impl Parser<Input<'s>, A, ContextError> {
     delimited(
         b"a:",
-        length_repeat(terminated(raw::size, b":{"), array_pair),
+        // Smallest pair is `i:0;N;`.
+        length_repeat(terminated(container_size(6), b":{"), (array_key, value)),
         b'}',
     )
-    .map(Value::Array)
-    .parse_next(input)
 }
 
-fn value_reference_to_value<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
+fn value_array<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
+    nested(array(any_value)).map(Value::Array).parse_next(input)
+}
+
+fn value_reference_to_value<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
     delimited(b"R:", raw::unsigned_integer, b';')
         .parse_to()
         .map(Value::ValueReference)
         .parse_next(input)
 }
 
-fn value_reference_to_object<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
+fn value_reference_to_object<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
     delimited(b"r:", raw::unsigned_integer, b';')
         .parse_to()
         .map(Value::ObjectReference)
         .parse_next(input)
 }
 
-fn object_property_name<'s>(input: &mut &'s [u8]) -> Result<(ObjectPropertyVisibility, &'s [u8])> {
+fn property_visibility<'s>(
+    input: &mut &'s [u8],
+) -> Result<(ObjectPropertyVisibility<'s>, &'s [u8])> {
     use ObjectPropertyVisibility::{Private, Protected, Public};
 
-    delimited(
-        b"s:",
-        raw::sized_string.and_then((
-            alt((
-                b"\0*\0".value(Protected),
-                (b'\0', take_until(1.., b'\0'), b'\0').value(Private),
-                empty.value(Public),
-            ))
-            .context(StrContext::Label("property visibility")),
-            rest,
-        )),
-        b';',
+    (
+        alt((
+            b"\0*\0".value(Protected),
+            delimited(b'\0', take_until(1.., b'\0'), b'\0')
+                .map(|class_name| Private(Cow::Borrowed(class_name))),
+            empty.value(Public),
+        ))
+        .context(StrContext::Label("property visibility")),
+        rest,
     )
+        .parse_next(input)
+}
+
+fn object_property_name<'s>(
+    input: &mut Input<'s>,
+) -> Result<(ObjectPropertyVisibility<'s>, Cow<'s, [u8]>)> {
+    dispatch! {peek(any);
+        b's' => delimited(b"s:", raw::sized_string.and_then(property_visibility), b';')
+            .map(|(visibility, name)| (visibility, Cow::Borrowed(name))),
+        b'S' => delimited(b"S:", raw::escaped_string, b';').verify_map(|name| {
+            let (visibility, name) = property_visibility.parse(&name).ok()?;
+            Some((visibility.into_owned(), Cow::Owned(name.to_vec())))
+        }),
+        _ => fail.context(StrContext::Label("property name")),
+    }
     .parse_next(input)
 }
 
-fn object_property<'s>(input: &mut &'s [u8]) -> Result<ObjectProperty<'s>> {
+fn object_property<'s>(input: &mut Input<'s>) -> Result<ObjectProperty<'s>> {
     (object_property_name, any_value)
         .map(|((visibility, name), value)| ObjectProperty {
             visibility,
@@ -132,86 +429,225 @@ fn object_property<'s>(input: &mut &'s [u8]) -> Result<ObjectProperty<'s>> {
         .parse_next(input)
 }
 
-fn value_object<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
-    seq!(
+fn value_object<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
+    nested(seq!(
         _: b"O:",
         raw::sized_string
         .context(StrContext::Label("class name")),
         _: b':',
+        // Smallest property is `s:0:"";N;`.
         length_repeat(
-            terminated(raw::size, b":{"),
+            terminated(container_size(9), b":{"),
             object_property.context(StrContext::Label("object property")),
         ).context(StrContext::Label("object properties")),
         _: b'}',
-    )
+    ))
     .map(|(class_name, properties)| Value::Object {
-        class_name,
+        class_name: Cow::Borrowed(class_name),
         properties,
     })
     .parse_next(input)
 }
 
-fn value_custom_object<'s>(input: &mut &'s [u8]) -> Result<Value<'s>> {
+fn value_custom_object<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
     seq!(
         _: b"C:",
         raw::sized_string
         .context(StrContext::Label("class name")),
         _: b':',
         length_take(
-            terminated(raw::size, b":{"),
+            terminated(raw::string_size, b":{"),
         ).context(StrContext::Label("object properties")),
         _: b'}',
     )
-    .map(|(class_name, data)| Value::CustomObject { class_name, data })
+    .map(|(class_name, data)| Value::CustomObject {
+        class_name: Cow::Borrowed(class_name),
+        data: Cow::Borrowed(data),
+    })
+    .parse_next(input)
+}
+
+fn value_enum<'s>(input: &mut Input<'s>) -> Result<Value<'s>> {
+    delimited(
+        b"E:",
+        raw::sized_string.and_then(separated_pair(
+            take_until(1.., b':').context(StrContext::Label("class name")),
+            b':',
+            rest.verify(|case: &[u8]| !case.is_empty())
+                .context(StrContext::Label("enum case")),
+        )),
+        b';',
+    )
+    .map(|(class_name, case)| Value::Enum {
+        class_name: Cow::Borrowed(class_name),
+        case: Cow::Borrowed(case),
+    })
     .parse_next(input)
 }
 
+/// Encoded bytes of a value.
+pub(crate) fn raw_value<'s>(input: &mut Input<'s>) -> Result<&'s [u8]> {
+    (|input: &mut Input<'s>| validate::validate_value(input, &mut validate::Stats::default()))
+        .take()
+        .parse_next(input)
+}
+
 /// Decode PHP serialize/unserialize formated binary string,
 ///
 /// # Errors
 ///
 /// Will return `Err` if input is not a valid PHP serialize value.
-pub fn unserialize(input: &[u8]) -> Result<Value<'_>, ParseError<&[u8], ContextError>> {
-    any_value.parse(input)
+pub fn unserialize(input: &[u8]) -> Result<Value<'_>, Error> {
+    unserialize_with(input, &DecodeOptions::default())
 }
 
-fn session_key<'s>(input: &mut &'s [u8]) -> Result<&'s [u8]> {
+/// Decode PHP serialize/unserialize formated binary string, using the given options.
+///
+/// # Errors
+///
+/// Will return `Err` if input is not a valid PHP serialize value, or if it exceeds the limits set
+/// by `options`.
+pub fn unserialize_with<'s>(input: &'s [u8], options: &DecodeOptions) -> Result<Value<'s>, Error> {
+    decode(input, options, any_value)
+}
+
+fn session_key<'s>(input: &mut Input<'s>) -> Result<&'s [u8]> {
     take_until(0.., '|').parse_next(input)
 }
 
+/// `key|value` entry, or `!key|` for an undefined variable.
+type Entry<'s, V> = (Cow<'s, [u8]>, Option<V>);
+
+fn session_entry<'s, V>(
+    mut value: impl Parser<Input<'s>, V, ContextError>,
+) -> impl Parser<Input<'s>, Entry<'s, V>, ContextError> {
+    move |input: &mut Input<'s>| {
+        let undefined = opt(b'!').parse_next(input)?.is_some();
+        let key = terminated(session_key, '|').parse_next(input)?;
+        let value = if undefined {
+            None
+        } else {
+            Some(value.parse_next(input)?)
+        };
+        Ok((Cow::Borrowed(key), value))
+    }
+}
+
+/// Flag set on the key length of `php_binary` entries holding an undefined variable.
+const BINARY_UNDEFINED: u8 = 0x80;
+
+fn binary_session_entry<'s, V>(
+    mut value: impl Parser<Input<'s>, V, ContextError>,
+) -> impl Parser<Input<'s>, Entry<'s, V>, ContextError> {
+    move |input: &mut Input<'s>| {
+        let length = any.parse_next(input)?;
+        let key_length = usize::from(length & !BINARY_UNDEFINED);
+        if input.eof_offset() < key_length {
+            // Report the truncated key at the end of input.
+            input.finish();
+        }
+        let key = take(key_length)
+            .context(StrContext::Label("session key"))
+            .parse_next(input)?;
+        let value = if length & BINARY_UNDEFINED == 0 {
+            Some(value.parse_next(input)?)
+        } else {
+            None
+        };
+        Ok((Cow::Borrowed(key), value))
+    }
+}
+
+/// Split a session into its entries, values being read by `value`.
+pub(crate) fn session<'s, V>(
+    input: &'s [u8],
+    options: &DecodeOptions,
+    value: impl Parser<Input<'s>, V, ContextError>,
+) -> Result<Vec<Entry<'s, V>>, Error> {
+    match options.session_format {
+        SessionFormat::Php => decode(
+            input,
+            options,
+            repeat_till(0.., session_entry(value), eof).map(|(entries, _)| entries),
+        ),
+        // PHP stores an empty session as an empty file.
+        SessionFormat::PhpSerialize if input.is_empty() => Ok(Vec::new()),
+        SessionFormat::PhpSerialize => decode(
+            input,
+            options,
+            array(value).map(|items: Vec<_>| {
+                items
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = match key {
+                            ArrayKey::Integer(n) => Cow::Owned(n.to_string().into_bytes()),
+                            ArrayKey::String(key) => key,
+                        };
+                        (key, Some(value))
+                    })
+                    .collect()
+            }),
+        ),
+        SessionFormat::PhpBinary => decode(
+            input,
+            options,
+            repeat_till(0.., binary_session_entry(value), eof).map(|(entries, _)| entries),
+        ),
+    }
+}
+
 /// Decode PHP session binary representation.
 ///
 /// # Errors
 ///
 /// Will return `Err` if input is not a valid PHP session.
-pub fn session_decode(
-    input: &[u8],
-) -> Result<Vec<SessionEntry<'_>>, ParseError<&[u8], ContextError>> {
-    repeat(
-        0..,
-        separated_pair(session_key, '|', any_value).map(|(key, value)| SessionEntry { key, value }),
-    )
-    .parse(input)
+pub fn session_decode(input: &[u8]) -> Result<Vec<SessionEntry<'_>>, Error> {
+    session_decode_with(input, &DecodeOptions::default())
+}
+
+/// Decode PHP session binary representation, using the given options.
+///
+/// # Errors
+///
+/// Will return `Err` if input is not a valid PHP session, or if it exceeds the limits set by
+/// `options`.
+pub fn session_decode_with<'s>(
+    input: &'s [u8],
+    options: &DecodeOptions,
+) -> Result<Vec<SessionEntry<'s>>, Error> {
+    let entries = session(input, options, any_value)?;
+    Ok(entries
+        .into_iter()
+        .map(|(key, value)| SessionEntry { key, value })
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    pub(crate) fn stream(input: &[u8]) -> Input<'_> {
+        Input {
+            input,
+            state: State::new(DecodeOptions::default()),
+        }
+    }
+
     #[allow(clippy::type_complexity)]
     pub(crate) fn run_cases<'s, O, E>(
-        mut parser: impl Parser<&'s [u8], O, E>,
+        mut parser: impl Parser<Input<'s>, O, E>,
         cases: &[(&'s [u8], Option<(&[u8], O)>)],
     ) where
         O: std::fmt::Debug + PartialEq,
         E: std::fmt::Debug + PartialEq,
     {
-        for (mut input, expected) in cases {
+        for (input, expected) in cases {
+            let mut input = stream(input);
             let output = parser.parse_next(&mut input);
             match expected {
                 Some(expected) => {
                     assert_eq!(output.as_ref(), Ok(&expected.1));
-                    assert_eq!(input, expected.0);
+                    assert_eq!(input.input, expected.0);
                 }
                 None => assert!(output.is_err()),
             }
@@ -336,14 +772,45 @@ mod tests {
                 b"d:-INF;".as_slice(),
                 Some((b"".as_slice(), Value::Decimal(f64::NEG_INFINITY))),
             ),
+            (
+                b"d:1.0E+25;".as_slice(),
+                Some((b"".as_slice(), Value::Decimal(1e25))),
+            ),
+            (
+                b"d:-1.5E-9;".as_slice(),
+                Some((b"".as_slice(), Value::Decimal(-1.5e-9))),
+            ),
+            (
+                b"d:.5;".as_slice(),
+                Some((b"".as_slice(), Value::Decimal(0.5))),
+            ),
+            (
+                b"d:+3;".as_slice(),
+                Some((b"".as_slice(), Value::Decimal(3.0))),
+            ),
+            (
+                b"d:7.;".as_slice(),
+                Some((b"".as_slice(), Value::Decimal(7.0))),
+            ),
+            (b"d:+INF;".as_slice(), None),
+            (b"d:-NAN;".as_slice(), None),
+            (b"d:1e;".as_slice(), None),
         ];
 
         run_cases(value_decimal, &cases);
     }
 
+    #[test]
+    fn parse_negative_zero_decimal_value() {
+        for input in [b"d:-0;".as_slice(), b"d:-0.0;", b"d:-.0e5;"] {
+            let output = value_decimal.parse_next(&mut stream(input)).unwrap();
+            assert!(matches!(output, Value::Decimal(d) if d == 0.0 && d.is_sign_negative()));
+        }
+    }
+
     #[test]
     fn parse_nan_decimal_value() {
-        let mut input = b"d:NAN;".as_slice();
+        let mut input = stream(b"d:NAN;");
 
         let output = value_decimal.parse_next(&mut input).unwrap();
         match output {
@@ -357,21 +824,49 @@ mod tests {
         let cases = [
             (
                 b"s:10:\"1234567890\";".as_slice(),
-                Some((b"".as_slice(), Value::String("1234567890".as_ref()))),
+                Some((
+                    b"".as_slice(),
+                    Value::String("1234567890".as_bytes().into()),
+                )),
             ),
             (
                 b"s:11:\"123456\"7890\";".as_slice(),
-                Some((b"".as_slice(), Value::String("123456\"7890".as_ref()))),
+                Some((
+                    b"".as_slice(),
+                    Value::String("123456\"7890".as_bytes().into()),
+                )),
             ),
             (
                 b"s:12:\"123456\";7890\";".as_slice(),
-                Some((b"".as_slice(), Value::String("123456\";7890".as_ref()))),
+                Some((
+                    b"".as_slice(),
+                    Value::String("123456\";7890".as_bytes().into()),
+                )),
             ),
         ];
 
         run_cases(value_string, &cases);
     }
 
+    #[test]
+    fn parse_escaped_string_value() {
+        let cases = [
+            (
+                b"S:3:\"foo\";".as_slice(),
+                Some((b"".as_slice(), Value::String(b"foo".into()))),
+            ),
+            (
+                b"S:4:\"\\00\\5c\\0a\\FF\";".as_slice(),
+                Some((b"".as_slice(), Value::String(b"\0\\\n\xff".into()))),
+            ),
+            (b"S:3:\"\\0xa\";".as_slice(), None),
+            (b"S:2:\"foo\";".as_slice(), None),
+            (b"S:4:\"foo\";".as_slice(), None),
+        ];
+
+        run_cases(value_string, &cases);
+    }
+
     #[test]
     fn parse_array_pair() {
         let cases = [
@@ -379,19 +874,25 @@ mod tests {
                 b"s:10:\"1234567890\";i:10;".as_slice(),
                 Some((
                     b"".as_slice(),
-                    (ArrayKey::String("1234567890".as_ref()), Value::Integer(10)),
+                    (
+                        ArrayKey::String("1234567890".as_bytes().into()),
+                        Value::Integer(10),
+                    ),
                 )),
             ),
             (
                 b"i:10;s:10:\"1234567890\";".as_slice(),
                 Some((
                     b"".as_slice(),
-                    (ArrayKey::Integer(10), Value::String("1234567890".as_ref())),
+                    (
+                        ArrayKey::Integer(10),
+                        Value::String("1234567890".as_bytes().into()),
+                    ),
                 )),
             ),
         ];
 
-        run_cases(array_pair, &cases);
+        run_cases((array_key, any_value), &cases);
     }
 
     #[test]
@@ -402,7 +903,7 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     Value::Array(vec![
-                        (ArrayKey::String(b"foo"), Value::String(b"bar"))
+                        (ArrayKey::String(b"foo".into()), Value::String(b"bar".into()))
                     ]),
                 )),
             ),
@@ -411,7 +912,7 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     Value::Array(vec![
-                        (ArrayKey::Integer(3), Value::String(b"baz"))
+                        (ArrayKey::Integer(3), Value::String(b"baz".into()))
                     ]),
                 )),
             ),
@@ -421,10 +922,10 @@ mod tests {
                     b"".as_slice(),
                     Value::Array(vec![
                         (ArrayKey::Integer(12), Value::Decimal(0.12)),
-                        (ArrayKey::String(b"foo"), Value::Array(vec![
+                        (ArrayKey::String(b"foo".into()), Value::Array(vec![
                             (
-                                ArrayKey::String(b"some-value"),
-                                Value::String(r#""other";"value""#.as_bytes())
+                                ArrayKey::String(b"some-value".into()),
+                                Value::String(r#""other";"value""#.as_bytes().into())
                             )
                         ])),
                         (ArrayKey::Integer(43), Value::Integer(76)),
@@ -444,7 +945,7 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     ObjectProperty {
-                        name: b"public".as_slice(),
+                        name: b"public".into(),
                         visibility: ObjectPropertyVisibility::Public,
                         value: Value::Integer(1),
                     },
@@ -455,7 +956,7 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     ObjectProperty {
-                        name: b"protected".as_slice(),
+                        name: b"protected".into(),
                         visibility: ObjectPropertyVisibility::Protected,
                         value: Value::Integer(42),
                     },
@@ -466,9 +967,20 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     ObjectProperty {
-                        name: b"private".as_slice(),
-                        visibility: ObjectPropertyVisibility::Private,
-                        value: Value::String(b"value".as_slice()),
+                        name: b"private".into(),
+                        visibility: ObjectPropertyVisibility::Private(b"ClassName".into()),
+                        value: Value::String(b"value".into()),
+                    },
+                )),
+            ),
+            (
+                b"S:13:\"\\00*\\00prot\\5cected\";S:1:\"\\0a\";".as_slice(),
+                Some((
+                    b"".as_slice(),
+                    ObjectProperty {
+                        name: b"prot\\ected".into(),
+                        visibility: ObjectPropertyVisibility::Protected,
+                        value: Value::String(b"\n".into()),
                     },
                 )),
             ),
@@ -485,21 +997,21 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     Value::Object {
-                        class_name: b"Test".as_slice(),
+                        class_name: b"Test".into(),
                         properties: vec![
                             ObjectProperty {
-                                name: b"public".as_slice(),
+                                name: b"public".into(),
                                 visibility: ObjectPropertyVisibility::Public,
                                 value: Value::Integer(1),
                             },
                             ObjectProperty {
-                                name: b"protected".as_slice(),
+                                name: b"protected".into(),
                                 visibility: ObjectPropertyVisibility::Protected,
                                 value: Value::Integer(2),
                             },
                             ObjectProperty {
-                                name: b"private".as_slice(),
-                                visibility: ObjectPropertyVisibility::Private,
+                                name: b"private".into(),
+                                visibility: ObjectPropertyVisibility::Private(b"Test".into()),
                                 value: Value::Integer(3),
                             },
                         ],
@@ -541,16 +1053,19 @@ mod tests {
             ),
             (
                 b"s:12:\"123456\";7890\";".as_slice(),
-                Some((b"".as_slice(), Value::String("123456\";7890".as_ref()))),
+                Some((
+                    b"".as_slice(),
+                    Value::String("123456\";7890".as_bytes().into()),
+                )),
             ),
             (
                 b"O:7:\"MyClass\":1:{s:10:\"myProperty\";i:42;}".as_slice(),
                 Some((
                     b"".as_slice(),
                     Value::Object {
-                        class_name: b"MyClass".as_slice(),
+                        class_name: b"MyClass".into(),
                         properties: vec![ObjectProperty {
-                            name: b"myProperty".as_slice(),
+                            name: b"myProperty".into(),
                             visibility: ObjectPropertyVisibility::Public,
                             value: Value::Integer(42),
                         }],
@@ -562,7 +1077,7 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     Value::Array(vec![
-                        (ArrayKey::Integer(0), Value::String(b"foo".as_slice())),
+                        (ArrayKey::Integer(0), Value::String(b"foo".into())),
                         (
                             ArrayKey::Integer(1),
                             Value::ValueReference(2.try_into().unwrap()),
@@ -575,9 +1090,9 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     Value::Object {
-                        class_name: b"stdClass".as_slice(),
+                        class_name: b"stdClass".into(),
                         properties: vec![ObjectProperty {
-                            name: b"foo".as_slice(),
+                            name: b"foo".into(),
                             visibility: ObjectPropertyVisibility::Public,
                             value: Value::ObjectReference(1.try_into().unwrap()),
                         }],
@@ -589,8 +1104,8 @@ mod tests {
                 Some((
                     b"".as_slice(),
                     Value::CustomObject {
-                        class_name: b"CustomSerializableClass".as_slice(),
-                        data: b"foobar".as_slice(),
+                        class_name: b"CustomSerializableClass".into(),
+                        data: b"foobar".into(),
                     },
                 )),
             ),
@@ -605,6 +1120,38 @@ mod tests {
         run_cases(any_value, &cases);
     }
 
+    #[test]
+    fn parse_enum() {
+        let cases = [
+            (
+                b"E:20:\"App\\Status:Suspended\";".as_slice(),
+                Some((
+                    b"".as_slice(),
+                    Value::Enum {
+                        class_name: b"App\\Status".into(),
+                        case: b"Suspended".into(),
+                    },
+                )),
+            ),
+            (
+                b"E:13:\"Status:Active\";extra".as_slice(),
+                Some((
+                    b"extra".as_slice(),
+                    Value::Enum {
+                        class_name: b"Status".into(),
+                        case: b"Active".into(),
+                    },
+                )),
+            ),
+            (b"E:6:\"Status\";".as_slice(), None),
+            (b"E:7:\":Active\";".as_slice(), None),
+            (b"E:7:\"Status:\";".as_slice(), None),
+            (b"E:13:\"Status:Active\"".as_slice(), None),
+        ];
+
+        run_cases(value_enum, &cases);
+    }
+
     #[test]
     fn decode_session() {
         let cases = [
@@ -617,8 +1164,8 @@ mod tests {
                 Some(
                     vec![
                         SessionEntry {
-                            key: b"foo",
-                            value: Value::Integer(42),
+                            key: b"foo".into(),
+                            value: Some(Value::Integer(42)),
                         },
                     ],
                 ),
@@ -628,8 +1175,8 @@ mod tests {
                 Some(
                     vec![
                         SessionEntry {
-                            key: b"",
-                            value: Value::String(b"okay"),
+                            key: b"".into(),
+                            value: Some(Value::String(b"okay".into())),
                         },
                     ],
                 ),
@@ -639,12 +1186,12 @@ mod tests {
                 Some(
                     vec![
                         SessionEntry {
-                            key: b"",
-                            value: Value::String(b"okay"),
+                            key: b"".into(),
+                            value: Some(Value::String(b"okay".into())),
                         },
                         SessionEntry {
-                            key: b"foo",
-                            value: Value::Integer(42),
+                            key: b"foo".into(),
+                            value: Some(Value::Integer(42)),
                         },
                     ],
                 ),
@@ -654,21 +1201,21 @@ mod tests {
                 Some(
                     vec![
                         SessionEntry {
-                            key: b"",
-                            value: Value::String(b"okay"),
+                            key: b"".into(),
+                            value: Some(Value::String(b"okay".into())),
                         },
                         SessionEntry {
-                            key: b"foo",
-                            value: Value::Integer(42),
+                            key: b"foo".into(),
+                            value: Some(Value::Integer(42)),
                         },
                         SessionEntry {
-                            key: b"a:1{\"not an array\"}",
-                            value: Value::Array(vec![
+                            key: b"a:1{\"not an array\"}".into(),
+                            value: Some(Value::Array(vec![
                                 (
-                                    ArrayKey::String(b"some-value"),
-                                    Value::String(r#""other";"value""#.as_bytes())
+                                    ArrayKey::String(b"some-value".into()),
+                                    Value::String(r#""other";"value""#.as_bytes().into())
                                 )
-                            ]),
+                            ])),
                         },
                     ],
                 ),
@@ -678,9 +1225,183 @@ mod tests {
         for (input, expected) in cases {
             let output = session_decode(input);
             match expected {
-                Some(expected) => assert_eq!(output, Ok(expected)),
+                Some(expected) => assert_eq!(output.ok(), Some(expected)),
                 None => assert!(output.is_err()),
             }
         }
     }
+
+    #[test]
+    fn decode_undefined_session_entries() {
+        assert_eq!(
+            session_decode(b"!foo|bar|i:1;!|!baz!|").ok(),
+            Some(vec![
+                SessionEntry {
+                    key: b"foo".into(),
+                    value: None,
+                },
+                SessionEntry {
+                    key: b"bar".into(),
+                    value: Some(Value::Integer(1)),
+                },
+                SessionEntry {
+                    key: b"".into(),
+                    value: None,
+                },
+                SessionEntry {
+                    key: b"baz!".into(),
+                    value: None,
+                },
+            ])
+        );
+        assert!(session_decode(b"!foo").is_err());
+    }
+
+    #[test]
+    fn decode_php_serialize_session() {
+        let options = DecodeOptions::new().with_session_format(SessionFormat::PhpSerialize);
+        assert_eq!(session_decode_with(b"", &options).ok(), Some(vec![]));
+        assert_eq!(session_decode_with(b"a:0:{}", &options).ok(), Some(vec![]));
+        assert_eq!(
+            session_decode_with(b"a:2:{s:3:\"foo\";i:42;i:7;s:3:\"bar\";}", &options).ok(),
+            Some(vec![
+                SessionEntry {
+                    key: b"foo".into(),
+                    value: Some(Value::Integer(42)),
+                },
+                SessionEntry {
+                    key: b"7".into(),
+                    value: Some(Value::String(b"bar".into())),
+                },
+            ])
+        );
+        assert!(session_decode_with(b"foo|i:42;", &options).is_err());
+        assert!(matches!(
+            session_decode_with(b"a:1:{s:3:\"foo\";i:42;}i:1;", &options),
+            Err(Error::TrailingData { offset: 21 })
+        ));
+    }
+
+    #[test]
+    fn decode_php_binary_session() {
+        let options = DecodeOptions::new().with_session_format(SessionFormat::PhpBinary);
+        assert_eq!(session_decode_with(b"", &options).ok(), Some(vec![]));
+        assert_eq!(
+            session_decode_with(b"\x03fooi:42;\x84gone\x00s:1:\"|\";", &options).ok(),
+            Some(vec![
+                SessionEntry {
+                    key: b"foo".into(),
+                    value: Some(Value::Integer(42)),
+                },
+                SessionEntry {
+                    key: b"gone".into(),
+                    value: None,
+                },
+                SessionEntry {
+                    key: b"".into(),
+                    value: Some(Value::String(b"|".into())),
+                },
+            ])
+        );
+        assert!(matches!(
+            session_decode_with(b"\x03fooi:42;\x05bar", &options),
+            Err(Error::UnexpectedEof {
+                offset: 13,
+                expected: Some("session key")
+            })
+        ));
+        assert!(session_decode_with(b"\x03foo", &options).is_err());
+    }
+
+    #[test]
+    fn decode_numeric_string_keys() {
+        let input = b"a:4:{s:2:\"42\";N;s:3:\"042\";N;s:2:\"-0\";N;S:2:\"-\\37\";N;}";
+        let keys = |options: &DecodeOptions| match unserialize_with(input, options) {
+            Ok(Value::Array(items)) => Some(items.into_iter().map(|(key, _)| key).collect()),
+            _ => None,
+        };
+        let string = |key: &'static [u8]| ArrayKey::String(key.into());
+
+        assert_eq!(
+            keys(&DecodeOptions::new()),
+            Some(vec![
+                string(b"42"),
+                string(b"042"),
+                string(b"-0"),
+                string(b"-7")
+            ])
+        );
+        let options = DecodeOptions::new().with_numeric_keys(NumericKeys::Canonicalize);
+        assert_eq!(
+            keys(&options),
+            Some(vec![
+                ArrayKey::Integer(42),
+                string(b"042"),
+                string(b"-0"),
+                ArrayKey::Integer(-7)
+            ])
+        );
+        let options = DecodeOptions::new().with_numeric_keys(NumericKeys::Reject);
+        assert!(matches!(
+            unserialize_with(input, &options),
+            Err(Error::NumericStringKey { offset: 5, key: 42 })
+        ));
+        assert!(matches!(
+            unserialize_with(b"a:1:{i:0;a:1:{S:2:\"-\\37\";N;}}", &options),
+            Err(Error::NumericStringKey {
+                offset: 14,
+                key: -7
+            })
+        ));
+
+        let options = options.with_session_format(SessionFormat::PhpSerialize);
+        assert!(matches!(
+            session_decode_with(b"a:1:{s:1:\"7\";N;}", &options),
+            Err(Error::NumericStringKey { offset: 5, key: 7 })
+        ));
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert!(matches!(
+            unserialize(b"a:1:{i:0;i:1"),
+            Err(Error::UnexpectedEof {
+                offset: 12,
+                expected: None
+            })
+        ));
+        assert!(matches!(
+            unserialize(b"a:1:{i:0;x:1;}"),
+            Err(Error::InvalidToken {
+                offset: 9,
+                expected: Some("value type")
+            })
+        ));
+        assert!(matches!(
+            unserialize(b"s:5:\"foo\";N;"),
+            Err(Error::LengthMismatch {
+                offset: 10,
+                declared: 5
+            })
+        ));
+        assert!(matches!(
+            unserialize(b"S:1:\"\\00x\";"),
+            Err(Error::LengthMismatch {
+                offset: 8,
+                declared: 1
+            })
+        ));
+        assert!(matches!(
+            unserialize(b"i:1;i:2;"),
+            Err(Error::TrailingData { offset: 4 })
+        ));
+        assert!(matches!(
+            unserialize_with(b"a:1:{i:0;a:0:{}}", &DecodeOptions::new().with_max_depth(1)),
+            Err(Error::DepthExceeded { offset: 9, max: 1 })
+        ));
+        assert!(matches!(
+            session_decode(b"foo|i:1;bar|"),
+            Err(Error::UnexpectedEof { offset: 12, .. })
+        ));
+    }
 }
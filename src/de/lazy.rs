@@ -0,0 +1,199 @@
+use std::borrow::Cow;
+
+use super::{any_value, decode, raw_value, session, DecodeOptions};
+use crate::{error::Error, value::Value};
+
+/// Entry of a [`LazySession`], holding its value still encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LazyEntry<'s> {
+    pub key: Cow<'s, [u8]>,
+    /// Encoded value, `None` for variables that were undefined when the session was written.
+    pub raw_value: Option<&'s [u8]>,
+}
+
+/// Session split into its entries, each value being decoded only when requested.
+///
+/// Splitting skips over values, reading strings by their declared length and building no arrays
+/// nor objects, so looking up a single key costs much less than [`session_decode`] when other
+/// entries are large.
+///
+/// ```
+/// use muesli::{LazySession, Value};
+///
+/// let session = LazySession::new(b"cart|a:1:{i:0;s:3:\"foo\";}user_id|i:42;").unwrap();
+/// assert_eq!(session.len(), 2);
+/// assert_eq!(session.get(b"user_id").unwrap(), Some(Value::Integer(42)));
+/// ```
+///
+/// [`session_decode`]: crate::session_decode
+#[derive(Debug, Clone)]
+pub struct LazySession<'s> {
+    input: &'s [u8],
+    options: DecodeOptions,
+    entries: Vec<LazyEntry<'s>>,
+}
+
+impl<'s> LazySession<'s> {
+    /// Split `input` into its entries.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if input is not a valid PHP session.
+    pub fn new(input: &'s [u8]) -> Result<Self, Error> {
+        Self::with_options(input, &DecodeOptions::default())
+    }
+
+    /// Split `input` into its entries, using the given options for splitting and decoding.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if input is not a valid PHP session, or if it exceeds the limits set by
+    /// `options`.
+    pub fn with_options(input: &'s [u8], options: &DecodeOptions) -> Result<Self, Error> {
+        let entries = session(input, options, raw_value)?
+            .into_iter()
+            .map(|(key, raw_value)| LazyEntry { key, raw_value })
+            .collect();
+        Ok(Self {
+            input,
+            options: options.clone(),
+            entries,
+        })
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[LazyEntry<'s>] {
+        &self.entries
+    }
+
+    /// Entry with the given key, the last one if the key is repeated, as PHP keeps it.
+    #[must_use]
+    pub fn entry(&self, key: &[u8]) -> Option<&LazyEntry<'s>> {
+        self.entries.iter().rev().find(|entry| *entry.key == *key)
+    }
+
+    /// Decode the value of the entry with the given key, `None` if there is no such entry or if
+    /// its variable was undefined.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the value exceeds the limits set by the options.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Value<'s>>, Error> {
+        match self.entry(key) {
+            Some(entry) => self.decode(entry),
+            None => Ok(None),
+        }
+    }
+
+    /// Decode the value of `entry`, `None` if its variable was undefined.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the value exceeds the limits set by the options. Offsets of errors are
+    /// relative to the whole session.
+    pub fn decode(&self, entry: &LazyEntry<'s>) -> Result<Option<Value<'s>>, Error> {
+        let Some(raw_value) = entry.raw_value else {
+            return Ok(None);
+        };
+        decode(raw_value, &self.options, any_value)
+            .map(Some)
+            .map_err(|err| err.shift_offset(self.offset_of(raw_value)))
+    }
+
+    /// Offset of `bytes` within the input, when they come from it.
+    fn offset_of(&self, bytes: &[u8]) -> usize {
+        let start = self.input.as_ptr() as usize;
+        (bytes.as_ptr() as usize)
+            .checked_sub(start)
+            .filter(|offset| *offset <= self.input.len())
+            .unwrap_or_default()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{session_decode_with, SessionFormat};
+
+    #[test]
+    fn split_session() {
+        let input =
+            b"a|O:3:\"Foo\":1:{s:4:\"\0*\0x\";a:1:{i:0;s:3:\"}|;\";}}!b|c|S:2:\"\\7c;\";a|N;";
+        let session = LazySession::new(input).unwrap();
+        let raw_values: Vec<_> = session
+            .entries()
+            .iter()
+            .map(|entry| (&*entry.key, entry.raw_value))
+            .collect();
+        assert_eq!(
+            raw_values,
+            [
+                (
+                    b"a".as_slice(),
+                    Some(b"O:3:\"Foo\":1:{s:4:\"\0*\0x\";a:1:{i:0;s:3:\"}|;\";}}".as_slice())
+                ),
+                (b"b", None),
+                (b"c", Some(b"S:2:\"\\7c;\";")),
+                (b"a", Some(b"N;")),
+            ]
+        );
+
+        assert_eq!(session.get(b"a").unwrap(), Some(Value::Null));
+        assert_eq!(session.get(b"b").unwrap(), None);
+        assert_eq!(session.get(b"d").unwrap(), None);
+        assert_eq!(
+            session.get(b"c").unwrap(),
+            Some(Value::String(b"|;".into()))
+        );
+        let entries = session_decode_with(input, &DecodeOptions::new()).unwrap();
+        assert_eq!(
+            session.decode(&session.entries()[0]).unwrap(),
+            entries[0].value
+        );
+    }
+
+    #[test]
+    fn split_session_formats() {
+        let options = DecodeOptions::new().with_session_format(SessionFormat::PhpSerialize);
+        let session =
+            LazySession::with_options(b"a:2:{i:7;b:1;s:1:\"x\";a:0:{}}", &options).unwrap();
+        assert_eq!(session.get(b"7").unwrap(), Some(Value::Boolean(true)));
+        assert_eq!(
+            session.entry(b"x").unwrap().raw_value,
+            Some(b"a:0:{}".as_slice())
+        );
+        assert!(LazySession::with_options(b"", &options).unwrap().is_empty());
+
+        let options = DecodeOptions::new().with_session_format(SessionFormat::PhpBinary);
+        let session = LazySession::with_options(b"\x01xi:1;\x81y", &options).unwrap();
+        assert_eq!(session.get(b"x").unwrap(), Some(Value::Integer(1)));
+        assert_eq!(session.entry(b"y").unwrap().raw_value, None);
+    }
+
+    #[test]
+    fn split_session_errors() {
+        assert!(matches!(
+            LazySession::new(b"a|a:1:{i:0;s:5:\"abc\";}"),
+            Err(Error::LengthMismatch {
+                offset: 21,
+                declared: 5
+            })
+        ));
+
+        let options = DecodeOptions::new().with_max_depth(1);
+        assert!(matches!(
+            LazySession::with_options(b"a|N;b|a:1:{i:0;a:0:{}}", &options),
+            Err(Error::DepthExceeded { offset: 15, max: 1 })
+        ));
+    }
+}
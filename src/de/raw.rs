@@ -1,30 +1,112 @@
 use winnow::{
-    binary::length_take,
-    combinator::{opt, terminated},
-    token::take_while,
+    combinator::{alt, opt, preceded, repeat, terminated},
+    error::StrContext,
+    stream::Stream,
+    token::{none_of, one_of, take, take_while},
     Parser, Result,
 };
 
-pub(crate) fn unsigned_integer<'s>(input: &mut &'s [u8]) -> Result<&'s [u8]> {
+use super::{fail_with, Cause, Input};
+use crate::error::LimitExceeded;
+
+pub(crate) fn unsigned_integer<'s>(input: &mut Input<'s>) -> Result<&'s [u8]> {
     take_while(1.., b'0'..=b'9').parse_next(input)
 }
 
-pub(crate) fn signed_integer<'s>(input: &mut &'s [u8]) -> Result<&'s [u8]> {
+pub(crate) fn signed_integer<'s>(input: &mut Input<'s>) -> Result<&'s [u8]> {
     (opt(b'-'), unsigned_integer).take().parse_next(input)
 }
 
-pub(crate) fn float<'s>(input: &mut &'s [u8]) -> Result<&'s [u8]> {
-    (signed_integer, opt((b'.', unsigned_integer)))
+/// Float as PHP reads it: a sign, digits with an optional fraction or a fraction alone, and an
+/// exponent, e.g. `-1`, `1.`, `+.5`, or `1.0E+25`.
+pub(crate) fn float<'s>(input: &mut Input<'s>) -> Result<&'s [u8]> {
+    (
+        opt(one_of([b'+', b'-'])),
+        alt((
+            (unsigned_integer, opt((b'.', take_while(0.., b'0'..=b'9')))).void(),
+            (b'.', unsigned_integer).void(),
+        )),
+        opt((
+            one_of([b'e', b'E']),
+            opt(one_of([b'+', b'-'])),
+            unsigned_integer,
+        )),
+    )
         .take()
         .parse_next(input)
 }
 
-pub(crate) fn size(input: &mut &[u8]) -> Result<usize> {
+pub(crate) fn size(input: &mut Input<'_>) -> Result<usize> {
     unsigned_integer.parse_to().parse_next(input)
 }
 
-pub(crate) fn sized_string<'s>(input: &mut &'s [u8]) -> Result<&'s [u8]> {
-    terminated(length_take(terminated(size, b":\"")), b'"').parse_next(input)
+/// Length declared by a string, bounded by the options and the remaining input.
+pub(crate) fn string_size(input: &mut Input<'_>) -> Result<usize> {
+    let checkpoint = input.checkpoint();
+    let declared = size.parse_next(input)?;
+    let max = input.state.options.max_string_len;
+    if declared > max {
+        input.reset(&checkpoint);
+        return fail_with(
+            input,
+            Cause::Limit(LimitExceeded::StringLength { declared, max }),
+        );
+    }
+    let remaining = input.eof_offset();
+    if declared > remaining {
+        input.reset(&checkpoint);
+        return fail_with(
+            input,
+            Cause::Limit(LimitExceeded::RemainingInput {
+                declared,
+                remaining,
+            }),
+        );
+    }
+    Ok(declared)
+}
+
+/// Closing quote expected right after the `declared` length of a string.
+fn string_end(input: &mut Input<'_>, declared: usize) -> Result<()> {
+    match input.first() {
+        Some(b'"') => {
+            input.next_token();
+            Ok(())
+        }
+        _ => fail_with(input, Cause::LengthMismatch { declared }),
+    }
+}
+
+pub(crate) fn sized_string<'s>(input: &mut Input<'s>) -> Result<&'s [u8]> {
+    let len = terminated(string_size, b":\"").parse_next(input)?;
+    let string = take(len).parse_next(input)?;
+    string_end(input, len)?;
+    Ok(string)
+}
+
+fn hex_byte(input: &mut Input<'_>) -> Result<u8> {
+    take_while(2, (b'0'..=b'9', b'a'..=b'f', b'A'..=b'F'))
+        .verify_map(|hex: &[u8]| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+        .parse_next(input)
+}
+
+/// Sized string where bytes may be written as `\xx` hex escapes, the size being the unescaped
+/// length.
+pub(crate) fn escaped_string(input: &mut Input<'_>) -> Result<Vec<u8>> {
+    let len = terminated(string_size, b":\"").parse_next(input)?;
+    let string = repeat(
+        len,
+        alt((
+            preceded(
+                b'\\',
+                hex_byte.context(StrContext::Label("escape sequence")),
+            ),
+            none_of(b'\\'),
+        )),
+    )
+    .parse_next(input)?;
+    string_end(input, len)?;
+    Ok(string)
 }
 
 #[cfg(test)]
@@ -94,8 +176,23 @@ mod tests {
                 b"-592.6".as_slice(),
                 Some((b"".as_slice(), b"-592.6".as_slice())),
             ),
+            (b"+3".as_slice(), Some((b"".as_slice(), b"+3".as_slice()))),
+            (b".5".as_slice(), Some((b"".as_slice(), b".5".as_slice()))),
+            (b"1.;".as_slice(), Some((b";".as_slice(), b"1.".as_slice()))),
+            (
+                b"1.0E+25".as_slice(),
+                Some((b"".as_slice(), b"1.0E+25".as_slice())),
+            ),
+            (
+                b"-1.5e-9".as_slice(),
+                Some((b"".as_slice(), b"-1.5e-9".as_slice())),
+            ),
+            (b"2e3".as_slice(), Some((b"".as_slice(), b"2e3".as_slice()))),
+            (b"2e;".as_slice(), Some((b"e;".as_slice(), b"2".as_slice()))),
             (b"".as_slice(), None),
             (b"a".as_slice(), None),
+            (b".".as_slice(), None),
+            (b"-.".as_slice(), None),
         ];
 
         run_cases(float, &cases);
@@ -138,4 +235,31 @@ mod tests {
 
         run_cases(sized_string, &cases);
     }
+
+    #[test]
+    fn parse_escaped_string() {
+        let cases = [
+            (b"0:\"\"".as_slice(), Some((b"".as_slice(), b"".to_vec()))),
+            (
+                b"3:\"abc\"".as_slice(),
+                Some((b"".as_slice(), b"abc".to_vec())),
+            ),
+            (
+                b"5:\"a\\00b\\5C\\ff\";".as_slice(),
+                Some((b";".as_slice(), b"a\0b\\\xff".to_vec())),
+            ),
+            (
+                b"3:\"a\"b\"".as_slice(),
+                Some((b"".as_slice(), b"a\"b".to_vec())),
+            ),
+            (b"2:\"\\0g\"".as_slice(), None),
+            (b"2:\"\\+f\"".as_slice(), None),
+            (b"1:\"\\0\"".as_slice(), None),
+            (b"2:\"abc\"".as_slice(), None),
+            (b"4:\"abc\"".as_slice(), None),
+            (b"2:\"\\00\"".as_slice(), None),
+        ];
+
+        run_cases(escaped_string, &cases);
+    }
 }
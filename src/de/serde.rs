@@ -0,0 +1,615 @@
+use std::{borrow::Cow, fmt};
+
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::{
+    error::Error,
+    value::{ArrayKey, ObjectProperty, ObjectPropertyVisibility, Value},
+};
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Deserialize an instance of `T` from PHP's `serialize` format.
+///
+/// PHP arrays map to Rust maps and structs, or to sequences when their keys are `0..n` in order.
+/// Objects map to structs, using property names without their visibility mangling, unless the
+/// struct has a field named after the mangled name, e.g. `#[serde(rename = "\0*\0foo")]`. `N;`
+/// maps to `None`.
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct User<'a> {
+///     name: &'a str,
+///     roles: Vec<String>,
+///     manager: Option<u32>,
+/// }
+///
+/// let user: User = muesli::from_bytes(
+///     b"a:3:{s:4:\"name\";s:5:\"alice\";s:5:\"roles\";a:1:{i:0;s:5:\"admin\";}s:7:\"manager\";N;}",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(
+///     user,
+///     User {
+///         name: "alice",
+///         roles: vec!["admin".to_string()],
+///         manager: None,
+///     }
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Will return `Err` if input is not a valid PHP serialize value, or if it doesn't match `T`.
+pub fn from_bytes<'de, T: de::Deserialize<'de>>(input: &'de [u8]) -> Result<T> {
+    from_value(super::unserialize(input)?)
+}
+
+/// Deserialize an instance of `T` from an already decoded [`Value`].
+///
+/// # Errors
+///
+/// Will return `Err` if value doesn't match `T`.
+pub fn from_value<'de, T: de::Deserialize<'de>>(value: Value<'de>) -> Result<T> {
+    T::deserialize(ValueDeserializer(value))
+}
+
+fn unexpected<'a>(value: &'a Value<'_>) -> Unexpected<'a> {
+    match value {
+        Value::Null => Unexpected::Unit,
+        Value::Boolean(b) => Unexpected::Bool(*b),
+        Value::Integer(n) => Unexpected::Signed(*n),
+        Value::Decimal(d) => Unexpected::Float(*d),
+        Value::String(s) => Unexpected::Bytes(s),
+        Value::Array(_) => Unexpected::Map,
+        Value::Object { .. } => Unexpected::Other("object"),
+        Value::CustomObject { .. } => Unexpected::Other("custom serialized object"),
+        Value::Enum { .. } => Unexpected::Other("enum case"),
+        Value::ValueReference(_) => Unexpected::Other("value reference"),
+        Value::ObjectReference(_) => Unexpected::Other("object reference"),
+    }
+}
+
+fn is_list(items: &[(ArrayKey, Value)]) -> bool {
+    items
+        .iter()
+        .zip(0..)
+        .all(|((key, _), idx)| *key == ArrayKey::Integer(idx))
+}
+
+fn visit_string<'de, V: Visitor<'de>>(bytes: Cow<'de, [u8]>, visitor: V) -> Result<V::Value> {
+    match bytes {
+        Cow::Borrowed(bytes) => match std::str::from_utf8(bytes) {
+            Ok(string) => visitor.visit_borrowed_str(string),
+            Err(_) => visitor.visit_borrowed_bytes(bytes),
+        },
+        Cow::Owned(bytes) => match String::from_utf8(bytes) {
+            Ok(string) => visitor.visit_string(string),
+            Err(err) => visitor.visit_byte_buf(err.into_bytes()),
+        },
+    }
+}
+
+fn visit_bytes<'de, V: Visitor<'de>>(bytes: Cow<'de, [u8]>, visitor: V) -> Result<V::Value> {
+    match bytes {
+        Cow::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+        Cow::Owned(bytes) => visitor.visit_byte_buf(bytes),
+    }
+}
+
+struct ValueDeserializer<'de>(Value<'de>);
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::Null => visitor.visit_unit(),
+            Value::Boolean(b) => visitor.visit_bool(b),
+            Value::Integer(n) => visitor.visit_i64(n),
+            Value::Decimal(d) => visitor.visit_f64(d),
+            Value::String(s) => visit_string(s, visitor),
+            Value::Array(items) if is_list(&items) => visitor.visit_seq(ListAccess {
+                items: items.into_iter(),
+            }),
+            Value::Array(items) => visitor.visit_map(ArrayAccess {
+                items: items.into_iter(),
+                value: None,
+            }),
+            Value::Object { properties, .. } => visitor.visit_map(ObjectAccess {
+                properties: properties.into_iter(),
+                fields: &[],
+                value: None,
+            }),
+            Value::Enum { case, .. } => visit_string(case, visitor),
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::String(s) => visit_bytes(s, visitor),
+            other => ValueDeserializer(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            other => visitor.visit_some(ValueDeserializer(other)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::Array(items) if is_list(&items) => visitor.visit_seq(ListAccess {
+                items: items.into_iter(),
+            }),
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::Array(items) => visitor.visit_map(ArrayAccess {
+                items: items.into_iter(),
+                value: None,
+            }),
+            other => ValueDeserializer(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            Value::Object { properties, .. } => visitor.visit_map(ObjectAccess {
+                properties: properties.into_iter(),
+                fields,
+                value: None,
+            }),
+            other => ValueDeserializer(other).deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            Value::String(variant) | Value::Enum { case: variant, .. } => {
+                visitor.visit_enum(Enum {
+                    variant,
+                    value: None,
+                })
+            }
+            Value::Array(items) if items.len() == 1 => match items.into_iter().next() {
+                Some((ArrayKey::String(variant), value)) => visitor.visit_enum(Enum {
+                    variant,
+                    value: Some(value),
+                }),
+                _ => Err(de::Error::custom(
+                    "expected an array with a single string key",
+                )),
+            },
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct identifier
+    }
+}
+
+struct ListAccess<'de> {
+    items: std::vec::IntoIter<(ArrayKey<'de>, Value<'de>)>,
+}
+
+impl<'de> SeqAccess<'de> for ListAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.items
+            .next()
+            .map(|(_, value)| seed.deserialize(ValueDeserializer(value)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct ArrayAccess<'de> {
+    items: std::vec::IntoIter<(ArrayKey<'de>, Value<'de>)>,
+    value: Option<Value<'de>>,
+}
+
+impl<'de> MapAccess<'de> for ArrayAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.items.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(KeyDeserializer(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(ValueDeserializer(value)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct ObjectAccess<'de> {
+    properties: std::vec::IntoIter<ObjectProperty<'de>>,
+    /// Fields of the struct being read, matched by mangled property names first.
+    fields: &'static [&'static str],
+    value: Option<Value<'de>>,
+}
+
+/// Name of a property as it appears in the input, e.g. `\0*\0foo` for a protected one.
+fn mangled_name(visibility: &ObjectPropertyVisibility, name: &[u8]) -> Vec<u8> {
+    let mut mangled = match visibility {
+        ObjectPropertyVisibility::Public => Vec::new(),
+        ObjectPropertyVisibility::Protected => b"\0*\0".to_vec(),
+        ObjectPropertyVisibility::Private(class_name) => [b"\0", &**class_name, b"\0"].concat(),
+    };
+    mangled.extend_from_slice(name);
+    mangled
+}
+
+impl<'de> MapAccess<'de> for ObjectAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.properties.next() {
+            Some(property) => {
+                self.value = Some(property.value);
+                let mangled = mangled_name(&property.visibility, &property.name);
+                let name = if self.fields.iter().any(|field| field.as_bytes() == mangled) {
+                    Cow::Owned(mangled)
+                } else {
+                    property.name
+                };
+                seed.deserialize(KeyDeserializer(ArrayKey::String(name)))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(ValueDeserializer(value)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.properties.len())
+    }
+}
+
+/// Array keys are either integers or strings, but Rust maps may expect the other kind.
+struct KeyDeserializer<'de>(ArrayKey<'de>);
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            ArrayKey::Integer(n) => visitor.visit_i64(n),
+            ArrayKey::String(s) => visit_string(s, visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            ArrayKey::Integer(n) => visitor.visit_string(n.to_string()),
+            ArrayKey::String(s) => visit_string(s, visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            ArrayKey::String(variant) => visitor.visit_enum(Enum {
+                variant,
+                value: None,
+            }),
+            ArrayKey::Integer(n) => Err(de::Error::invalid_type(Unexpected::Signed(n), &visitor)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf
+        option unit unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+struct Enum<'de> {
+    variant: Cow<'de, [u8]>,
+    value: Option<Value<'de>>,
+}
+
+impl<'de> EnumAccess<'de> for Enum<'de> {
+    type Error = Error;
+    type Variant = Variant<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Variant<'de>)> {
+        let variant = seed.deserialize(KeyDeserializer(ArrayKey::String(self.variant)))?;
+        Ok((variant, Variant(self.value)))
+    }
+}
+
+struct Variant<'de>(Option<Value<'de>>);
+
+impl<'de> VariantAccess<'de> for Variant<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.0 {
+            None | Some(Value::Null) => Ok(()),
+            Some(other) => Err(de::Error::invalid_type(unexpected(&other), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        match self.0 {
+            Some(value) => seed.deserialize(ValueDeserializer(value)),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Some(value) => de::Deserializer::deserialize_seq(ValueDeserializer(value), visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &visitor)),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            Some(value) => de::Deserializer::deserialize_map(ValueDeserializer(value), visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &visitor)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Item<'a> {
+        sku: &'a str,
+        quantity: u32,
+        price: f64,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Status {
+        Active,
+        Suspended { until: i64 },
+    }
+
+    #[test]
+    fn deserialize_scalars() {
+        assert_eq!(from_bytes::<i64>(b"i:-42;").ok(), Some(-42));
+        assert_eq!(from_bytes::<u8>(b"i:200;").ok(), Some(200));
+        assert!(from_bytes::<u8>(b"i:-1;").is_err());
+        assert_eq!(from_bytes::<bool>(b"b:1;").ok(), Some(true));
+        assert_eq!(from_bytes::<f64>(b"d:0.5;").ok(), Some(0.5));
+        assert_eq!(
+            from_bytes::<String>(b"s:3:\"foo\";").ok(),
+            Some("foo".to_string())
+        );
+        assert_eq!(
+            from_bytes::<&[u8]>(b"s:2:\"\xff\xfe\";").ok(),
+            Some(b"\xff\xfe".as_slice())
+        );
+        assert_eq!(from_bytes::<()>(b"N;").ok(), Some(()));
+    }
+
+    #[test]
+    fn deserialize_option() {
+        assert_eq!(from_bytes::<Option<i64>>(b"N;").ok(), Some(None));
+        assert_eq!(from_bytes::<Option<i64>>(b"i:3;").ok(), Some(Some(3)));
+    }
+
+    #[test]
+    fn deserialize_array_as_sequence() {
+        assert_eq!(
+            from_bytes::<Vec<i64>>(b"a:3:{i:0;i:4;i:1;i:5;i:2;i:6;}").ok(),
+            Some(vec![4, 5, 6])
+        );
+        assert_eq!(from_bytes::<Vec<i64>>(b"a:0:{}").ok(), Some(vec![]));
+        assert_eq!(
+            from_bytes::<(i64, String)>(b"a:2:{i:0;i:4;i:1;s:1:\"a\";}").ok(),
+            Some((4, "a".to_string()))
+        );
+        assert!(from_bytes::<Vec<i64>>(b"a:2:{i:1;i:4;i:0;i:5;}").is_err());
+        assert!(from_bytes::<Vec<i64>>(b"a:1:{s:1:\"a\";i:4;}").is_err());
+    }
+
+    #[test]
+    fn deserialize_array_as_map() {
+        let expected = BTreeMap::from([(3, "three".to_string()), (7, "seven".to_string())]);
+        assert_eq!(
+            from_bytes::<BTreeMap<i64, String>>(b"a:2:{i:3;s:5:\"three\";i:7;s:5:\"seven\";}").ok(),
+            Some(expected)
+        );
+
+        let expected = BTreeMap::from([("0".to_string(), 1), ("foo".to_string(), 2)]);
+        assert_eq!(
+            from_bytes::<BTreeMap<String, i64>>(b"a:2:{i:0;i:1;s:3:\"foo\";i:2;}").ok(),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn deserialize_array_as_struct() {
+        assert_eq!(
+            from_bytes::<Item>(
+                b"a:3:{s:3:\"sku\";s:4:\"A-42\";s:8:\"quantity\";i:2;s:5:\"price\";d:9.99;}"
+            )
+            .ok(),
+            Some(Item {
+                sku: "A-42",
+                quantity: 2,
+                price: 9.99,
+            })
+        );
+        assert!(from_bytes::<Item>(b"a:1:{s:3:\"sku\";s:4:\"A-42\";}").is_err());
+    }
+
+    #[test]
+    fn deserialize_object_as_struct() {
+        assert_eq!(
+            from_bytes::<Item>(
+                b"O:4:\"Item\":3:{s:3:\"sku\";s:4:\"A-42\";s:11:\"\0*\0quantity\";i:2;s:11:\"\0Item\0price\";i:10;}"
+            ).ok(),
+            Some(Item {
+                sku: "A-42",
+                quantity: 2,
+                price: 10.0,
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_enum() {
+        assert_eq!(
+            from_bytes::<Status>(b"s:6:\"Active\";").ok(),
+            Some(Status::Active)
+        );
+        assert_eq!(
+            from_bytes::<Status>(b"a:1:{s:9:\"Suspended\";a:1:{s:5:\"until\";i:1700000000;}}").ok(),
+            Some(Status::Suspended {
+                until: 1_700_000_000
+            })
+        );
+        assert!(from_bytes::<Status>(b"s:7:\"Unknown\";").is_err());
+        assert_eq!(
+            from_bytes::<Status>(b"E:17:\"App\\Status:Active\";").ok(),
+            Some(Status::Active)
+        );
+        assert_eq!(
+            from_bytes::<String>(b"E:17:\"App\\Status:Active\";").ok(),
+            Some("Active".to_string())
+        );
+    }
+
+    #[test]
+    fn deserialize_owned_value() {
+        let value = Value::Array(vec![(
+            ArrayKey::String(b"sku".to_vec().into()),
+            Value::String(b"A-42".to_vec().into()),
+        )]);
+        assert_eq!(
+            from_value::<BTreeMap<String, String>>(value).ok(),
+            Some(BTreeMap::from([("sku".to_string(), "A-42".to_string())]))
+        );
+    }
+
+    #[test]
+    fn deserialize_invalid_input() {
+        assert!(matches!(
+            from_bytes::<i64>(b"i:12"),
+            Err(Error::UnexpectedEof { offset: 4, .. })
+        ));
+        assert!(matches!(
+            from_bytes::<i64>(b"i:12;N;"),
+            Err(Error::TrailingData { offset: 5 })
+        ));
+        assert!(matches!(from_bytes::<i64>(b"R:1;"), Err(Error::Message(_))));
+    }
+}
@@ -0,0 +1,773 @@
+use std::{
+    io::{BufRead, Read},
+    num::NonZeroUsize,
+    str::FromStr,
+};
+
+use winnow::Parser;
+
+use super::{property_visibility, raw, DecodeOptions, Input, State};
+use crate::{
+    error::{Error, LimitExceeded},
+    value::{
+        canonical_integer, ArrayKey, NumericKeys, ObjectPropertyVisibility, OwnedValue, Value,
+    },
+};
+
+/// Longest number accepted, so that a run of digits can't grow memory without bounds.
+const MAX_NUMBER_LEN: usize = 1024;
+
+type NumberParser = for<'t> fn(&mut Input<'t>) -> winnow::Result<&'t [u8]>;
+
+/// Convert `token` if `parser` matches all of it.
+fn parse_number<O: FromStr>(token: &[u8], parser: NumberParser) -> Option<O> {
+    let mut input = Input {
+        input: token,
+        state: State::new(DecodeOptions::default()),
+    };
+    if parser(&mut input).is_err() || !input.input.is_empty() {
+        return None;
+    }
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+/// Step of the decoding of a PHP serialized value, see [`EventReader`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Start of an array declaring `len` elements, each made of a [`Event::Key`] and a value.
+    StartArray(usize),
+    /// Key of the next element of the current array.
+    Key(ArrayKey<'static>),
+    EndArray,
+    /// Start of an object declaring `len` properties, each made of an [`Event::Property`] and a
+    /// value.
+    StartObject {
+        class_name: Vec<u8>,
+        len: usize,
+    },
+    /// Name of the next property of the current object.
+    Property {
+        visibility: ObjectPropertyVisibility<'static>,
+        name: Vec<u8>,
+    },
+    EndObject,
+    /// Any value but an array or an object.
+    Scalar(OwnedValue),
+}
+
+/// Array or object being read.
+#[derive(Debug)]
+struct Frame {
+    object: bool,
+    remaining: usize,
+    value_next: bool,
+}
+
+/// Pull-based decoder reading a PHP serialized value from a [`BufRead`] as a sequence of
+/// [`Event`]s, without holding more than a single string in memory.
+///
+/// Limits of [`DecodeOptions`] apply, except declared sizes can't be checked against the
+/// remaining input. Nesting doesn't use the stack, so `max_depth` may be raised freely.
+///
+/// ```
+/// use muesli::{Event, EventReader, Value};
+///
+/// let input = b"a:1:{s:3:\"foo\";i:42;}".as_slice();
+/// let events: Vec<_> = EventReader::new(input).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(
+///     events,
+///     [
+///         Event::StartArray(1),
+///         Event::Key(muesli::ArrayKey::String(b"foo".into())),
+///         Event::Scalar(Value::Integer(42)),
+///         Event::EndArray,
+///     ]
+/// );
+/// ```
+#[derive(Debug)]
+pub struct EventReader<R> {
+    reader: R,
+    options: DecodeOptions,
+    offset: usize,
+    elements: usize,
+    stack: Vec<Frame>,
+    started: bool,
+    done: bool,
+}
+
+impl<R: BufRead> EventReader<R> {
+    /// Decode from `reader`, which may be a [`std::io::BufReader`] wrapping any [`Read`].
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, &DecodeOptions::default())
+    }
+
+    /// Decode from `reader`, using the given options.
+    pub fn with_options(reader: R, options: &DecodeOptions) -> Self {
+        Self {
+            reader,
+            options: options.clone(),
+            offset: 0,
+            elements: 0,
+            stack: Vec::new(),
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Number of bytes read so far.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Nesting depth of the next event, `0` being the top-level value.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read the next event, `None` once the value is complete and the input ended.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if read fail, if input is not a valid PHP serialized value, or if it
+    /// exceeds the limits set by the options. No more events are read after an error.
+    pub fn next_event(&mut self) -> Result<Option<Event>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+        let event = self.read_event();
+        if !matches!(event, Ok(Some(_))) {
+            self.done = true;
+        }
+        event
+    }
+
+    fn read_event(&mut self) -> Result<Option<Event>, Error> {
+        let Some(frame) = self.stack.last_mut() else {
+            if self.started {
+                return match self.peek()? {
+                    Some(_) => Err(Error::TrailingData {
+                        offset: self.offset,
+                    }),
+                    None => Ok(None),
+                };
+            }
+            self.started = true;
+            return self.value().map(Some);
+        };
+        let object = frame.object;
+        if frame.value_next {
+            frame.value_next = false;
+            return self.value().map(Some);
+        }
+        if frame.remaining == 0 {
+            self.tag(b"}")?;
+            self.stack.pop();
+            return Ok(Some(if object {
+                Event::EndObject
+            } else {
+                Event::EndArray
+            }));
+        }
+        frame.remaining -= 1;
+        frame.value_next = true;
+        if object {
+            self.property().map(Some)
+        } else {
+            self.key().map(Some)
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, Error> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn bump(&mut self) {
+        self.reader.consume(1);
+        self.offset += 1;
+    }
+
+    fn eof(&self, expected: Option<&'static str>) -> Error {
+        Error::UnexpectedEof {
+            offset: self.offset,
+            expected,
+        }
+    }
+
+    fn invalid(&self, expected: Option<&'static str>) -> Error {
+        Error::InvalidToken {
+            offset: self.offset,
+            expected,
+        }
+    }
+
+    fn next_byte(&mut self, expected: Option<&'static str>) -> Result<u8, Error> {
+        let byte = self.peek()?.ok_or_else(|| self.eof(expected))?;
+        self.bump();
+        Ok(byte)
+    }
+
+    fn tag(&mut self, tag: &[u8]) -> Result<(), Error> {
+        for &expected in tag {
+            match self.peek()? {
+                Some(byte) if byte == expected => self.bump(),
+                Some(_) => return Err(self.invalid(None)),
+                None => return Err(self.eof(None)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the bytes a number may be made of.
+    fn number_token(&mut self) -> Result<Vec<u8>, Error> {
+        let mut token = Vec::new();
+        while let Some(byte) = self.peek()? {
+            if !matches!(byte, b'0'..=b'9' | b'+' | b'-' | b'.' | b'e' | b'E') {
+                break;
+            }
+            if token.len() == MAX_NUMBER_LEN {
+                return Err(self.invalid(None));
+            }
+            token.push(byte);
+            self.bump();
+        }
+        Ok(token)
+    }
+
+    /// Read a number matching `parser`.
+    fn number<O: FromStr>(&mut self, parser: NumberParser) -> Result<O, Error> {
+        let offset = self.offset;
+        let token = self.number_token()?;
+        parse_number(&token, parser).ok_or(Error::InvalidToken {
+            offset,
+            expected: None,
+        })
+    }
+
+    fn integer(&mut self) -> Result<i64, Error> {
+        self.number(raw::signed_integer)
+    }
+
+    fn size(&mut self) -> Result<usize, Error> {
+        self.number(raw::unsigned_integer)
+    }
+
+    fn index(&mut self) -> Result<NonZeroUsize, Error> {
+        self.number(raw::unsigned_integer)
+    }
+
+    /// Length declared by a string, bounded by the options.
+    fn string_size(&mut self) -> Result<usize, Error> {
+        let offset = self.offset;
+        let declared = self.size()?;
+        let max = self.options.max_string_len;
+        if declared > max {
+            return Err(Error::LimitExceeded {
+                offset,
+                limit: LimitExceeded::StringLength { declared, max },
+            });
+        }
+        Ok(declared)
+    }
+
+    /// Number of elements declared by an array or object, bounded by the options.
+    fn container_size(&mut self) -> Result<usize, Error> {
+        let offset = self.offset;
+        let declared = self.size()?;
+        let max = self.options.max_container_len;
+        if declared > max {
+            return Err(Error::LimitExceeded {
+                offset,
+                limit: LimitExceeded::ContainerLength { declared, max },
+            });
+        }
+        let elements = self.elements.saturating_add(declared);
+        let max = self.options.max_total_elements;
+        if elements > max {
+            return Err(Error::LimitExceeded {
+                offset,
+                limit: LimitExceeded::TotalElements { max },
+            });
+        }
+        self.elements = elements;
+        Ok(declared)
+    }
+
+    /// Read `len` bytes, growing the buffer only as data actually comes in.
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        let read = (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)?;
+        self.offset += read;
+        if read < len {
+            return Err(self.eof(None));
+        }
+        Ok(bytes)
+    }
+
+    /// Closing quote expected right after the `declared` length of a string.
+    fn string_end(&mut self, declared: usize) -> Result<(), Error> {
+        if self.peek()? == Some(b'"') {
+            self.bump();
+            Ok(())
+        } else {
+            Err(Error::LengthMismatch {
+                offset: self.offset,
+                declared,
+            })
+        }
+    }
+
+    fn sized_string(&mut self) -> Result<Vec<u8>, Error> {
+        let len = self.string_size()?;
+        self.tag(b":\"")?;
+        let string = self.bytes(len)?;
+        self.string_end(len)?;
+        Ok(string)
+    }
+
+    fn escaped_string(&mut self) -> Result<Vec<u8>, Error> {
+        let len = self.string_size()?;
+        self.tag(b":\"")?;
+        let mut string = Vec::new();
+        for _ in 0..len {
+            let byte = match self.next_byte(None)? {
+                b'\\' => {
+                    let expected = Some("escape sequence");
+                    let mut hex = [0; 2];
+                    for digit in &mut hex {
+                        *digit = self.next_byte(expected)?;
+                    }
+                    std::str::from_utf8(&hex)
+                        .ok()
+                        .filter(|hex| hex.bytes().all(|digit| digit.is_ascii_hexdigit()))
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                        .ok_or_else(|| self.invalid(expected))?
+                }
+                byte => byte,
+            };
+            string.push(byte);
+        }
+        self.string_end(len)?;
+        Ok(string)
+    }
+
+    /// String value or key, `s:` or `S:`.
+    fn string(&mut self, expected: &'static str) -> Result<Vec<u8>, Error> {
+        let string = match self.peek()? {
+            Some(b's') => {
+                self.tag(b"s:")?;
+                self.sized_string()?
+            }
+            Some(b'S') => {
+                self.tag(b"S:")?;
+                self.escaped_string()?
+            }
+            Some(_) => return Err(self.invalid(Some(expected))),
+            None => return Err(self.eof(Some(expected))),
+        };
+        self.tag(b";")?;
+        Ok(string)
+    }
+
+    fn key(&mut self) -> Result<Event, Error> {
+        if self.peek()? == Some(b'i') {
+            self.tag(b"i:")?;
+            let key = self.integer()?;
+            self.tag(b";")?;
+            return Ok(Event::Key(ArrayKey::Integer(key)));
+        }
+        let offset = self.offset;
+        let key = self.string("array key")?;
+        let numeric_keys = self.options.numeric_keys;
+        match canonical_integer(&key) {
+            Some(n) if numeric_keys == NumericKeys::Canonicalize => {
+                Ok(Event::Key(ArrayKey::Integer(n)))
+            }
+            Some(n) if numeric_keys == NumericKeys::Reject => {
+                Err(Error::NumericStringKey { offset, key: n })
+            }
+            _ => Ok(Event::Key(ArrayKey::String(key.into()))),
+        }
+    }
+
+    fn property(&mut self) -> Result<Event, Error> {
+        let offset = self.offset;
+        let name = self.string("property name")?;
+        let (visibility, name) =
+            property_visibility
+                .parse(&name)
+                .map_err(|_| Error::InvalidToken {
+                    offset,
+                    expected: Some("property visibility"),
+                })?;
+        Ok(Event::Property {
+            visibility: visibility.into_owned(),
+            name: name.to_vec(),
+        })
+    }
+
+    /// Enter an array or object, failing once the maximum depth is reached.
+    fn push(&mut self, object: bool, len: usize) -> Result<(), Error> {
+        self.tag(b":{")?;
+        self.stack.push(Frame {
+            object,
+            remaining: len,
+            value_next: false,
+        });
+        Ok(())
+    }
+
+    fn check_depth(&self) -> Result<(), Error> {
+        let max = self.options.max_depth;
+        if self.stack.len() >= max {
+            return Err(Error::DepthExceeded {
+                offset: self.offset,
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    fn decimal(&mut self) -> Result<f64, Error> {
+        self.tag(b"d:")?;
+        let value = match self.peek()? {
+            Some(b'N') => {
+                self.tag(b"NAN")?;
+                f64::NAN
+            }
+            Some(b'I') => {
+                self.tag(b"INF")?;
+                f64::INFINITY
+            }
+            _ => {
+                let offset = self.offset;
+                let token = self.number_token()?;
+                if token == b"-" && self.peek()? == Some(b'I') {
+                    self.tag(b"INF")?;
+                    f64::NEG_INFINITY
+                } else {
+                    parse_number(&token, raw::float).ok_or(Error::InvalidToken {
+                        offset,
+                        expected: None,
+                    })?
+                }
+            }
+        };
+        self.tag(b";")?;
+        Ok(value)
+    }
+
+    fn custom_object(&mut self) -> Result<Value<'static>, Error> {
+        self.tag(b"C:")?;
+        let class_name = self.sized_string()?;
+        self.tag(b":")?;
+        let len = self.string_size()?;
+        self.tag(b":{")?;
+        let data = self.bytes(len)?;
+        self.tag(b"}")?;
+        Ok(Value::CustomObject {
+            class_name: class_name.into(),
+            data: data.into(),
+        })
+    }
+
+    fn enum_case(&mut self) -> Result<Value<'static>, Error> {
+        self.tag(b"E:")?;
+        let offset = self.offset;
+        let mut name = self.sized_string()?;
+        let (class_name, case) = match name.iter().position(|&byte| byte == b':') {
+            Some(separator) if separator > 0 && separator + 1 < name.len() => {
+                let case = name.split_off(separator + 1);
+                name.pop();
+                (name, case)
+            }
+            _ => {
+                return Err(Error::InvalidToken {
+                    offset,
+                    expected: Some("enum case"),
+                })
+            }
+        };
+        self.tag(b";")?;
+        Ok(Value::Enum {
+            class_name: class_name.into(),
+            case: case.into(),
+        })
+    }
+
+    fn value(&mut self) -> Result<Event, Error> {
+        let expected = Some("value type");
+        let value = match self.peek()?.ok_or_else(|| self.eof(expected))? {
+            b'N' => {
+                self.tag(b"N;")?;
+                Value::Null
+            }
+            b'b' => {
+                self.tag(b"b:")?;
+                let value = match self.peek()? {
+                    Some(b'0') => false,
+                    Some(b'1') => true,
+                    Some(_) => return Err(self.invalid(None)),
+                    None => return Err(self.eof(None)),
+                };
+                self.bump();
+                self.tag(b";")?;
+                Value::Boolean(value)
+            }
+            b'i' => {
+                self.tag(b"i:")?;
+                let value = self.integer()?;
+                self.tag(b";")?;
+                Value::Integer(value)
+            }
+            b'd' => Value::Decimal(self.decimal()?),
+            b's' | b'S' => Value::String(self.string("string")?.into()),
+            b'a' => {
+                self.check_depth()?;
+                self.tag(b"a:")?;
+                let len = self.container_size()?;
+                self.push(false, len)?;
+                return Ok(Event::StartArray(len));
+            }
+            b'O' => {
+                self.check_depth()?;
+                self.tag(b"O:")?;
+                let class_name = self.sized_string()?;
+                self.tag(b":")?;
+                let len = self.container_size()?;
+                self.push(true, len)?;
+                return Ok(Event::StartObject { class_name, len });
+            }
+            b'C' => self.custom_object()?,
+            b'E' => self.enum_case()?,
+            b'R' => {
+                self.tag(b"R:")?;
+                let index = self.index()?;
+                self.tag(b";")?;
+                Value::ValueReference(index)
+            }
+            b'r' => {
+                self.tag(b"r:")?;
+                let index = self.index()?;
+                self.tag(b";")?;
+                Value::ObjectReference(index)
+            }
+            _ => return Err(self.invalid(expected)),
+        };
+        Ok(Event::Scalar(value))
+    }
+}
+
+impl<R: BufRead> Iterator for EventReader<R> {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+
+    type Check = fn(&Error) -> bool;
+
+    fn events(input: &[u8], options: &DecodeOptions) -> Result<Vec<Event>, Error> {
+        EventReader::with_options(input, options).collect()
+    }
+
+    #[test]
+    fn read_events() {
+        let input = b"a:3:{i:0;O:3:\"Foo\":2:{s:6:\"\0Foo\0a\";d:1.5E+3;s:4:\"\0*\0b\";a:0:{}}S:1:\"\\78\";E:7:\"Foo:Bar\";i:-2;C:3:\"Foo\":2:{ab}}";
+        // A tiny buffer makes every token straddle reads.
+        let reader = BufReader::with_capacity(1, input.as_slice());
+        let events: Vec<_> = EventReader::new(reader).collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            events,
+            [
+                Event::StartArray(3),
+                Event::Key(ArrayKey::Integer(0)),
+                Event::StartObject {
+                    class_name: b"Foo".to_vec(),
+                    len: 2
+                },
+                Event::Property {
+                    visibility: ObjectPropertyVisibility::Private(b"Foo".into()),
+                    name: b"a".to_vec(),
+                },
+                Event::Scalar(Value::Decimal(1500.0)),
+                Event::Property {
+                    visibility: ObjectPropertyVisibility::Protected,
+                    name: b"b".to_vec(),
+                },
+                Event::StartArray(0),
+                Event::EndArray,
+                Event::EndObject,
+                Event::Key(ArrayKey::String(b"x".into())),
+                Event::Scalar(Value::Enum {
+                    class_name: b"Foo".into(),
+                    case: b"Bar".into(),
+                }),
+                Event::Key(ArrayKey::Integer(-2)),
+                Event::Scalar(Value::CustomObject {
+                    class_name: b"Foo".into(),
+                    data: b"ab".into(),
+                }),
+                Event::EndArray,
+            ]
+        );
+    }
+
+    #[test]
+    fn read_scalars() {
+        let options = DecodeOptions::new();
+        let cases = [
+            (b"N;".as_slice(), Value::Null),
+            (b"b:1;", Value::Boolean(true)),
+            (b"d:-INF;", Value::Decimal(f64::NEG_INFINITY)),
+            (b"d:.5;", Value::Decimal(0.5)),
+            (b"s:3:\"a\"b\";", Value::String(b"a\"b".into())),
+            (
+                b"R:2;",
+                Value::ValueReference(NonZeroUsize::new(2).unwrap()),
+            ),
+            (b"r:1;", Value::ObjectReference(NonZeroUsize::MIN)),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                events(input, &options).ok(),
+                Some(vec![Event::Scalar(expected)])
+            );
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        let options = DecodeOptions::new();
+        let cases: [(&[u8], Check); 10] = [
+            (b"", |err| {
+                matches!(
+                    err,
+                    Error::UnexpectedEof {
+                        offset: 0,
+                        expected: Some("value type")
+                    }
+                )
+            }),
+            (b"x", |err| {
+                matches!(err, Error::InvalidToken { offset: 0, .. })
+            }),
+            (b"b:2;", |err| {
+                matches!(err, Error::InvalidToken { offset: 2, .. })
+            }),
+            (b"i:1x;", |err| {
+                matches!(err, Error::InvalidToken { offset: 3, .. })
+            }),
+            (b"i:99999999999999999999;", |err| {
+                matches!(err, Error::InvalidToken { offset: 2, .. })
+            }),
+            (b"s:9:\"abc\";", |err| {
+                matches!(err, Error::UnexpectedEof { offset: 10, .. })
+            }),
+            (b"s:2:\"abc\";", |err| {
+                matches!(
+                    err,
+                    Error::LengthMismatch {
+                        offset: 7,
+                        declared: 2
+                    }
+                )
+            }),
+            (b"a:2:{i:0;N;}", |err| {
+                matches!(err, Error::InvalidToken { offset: 11, .. })
+            }),
+            (b"E:4:\"Foo:\";", |err| {
+                matches!(err, Error::InvalidToken { offset: 2, .. })
+            }),
+            (b"N;N;", |err| {
+                matches!(err, Error::TrailingData { offset: 2 })
+            }),
+        ];
+        for (input, check) in cases {
+            let err = events(input, &options).unwrap_err();
+            assert!(check(&err), "{}: {err:?}", String::from_utf8_lossy(input));
+        }
+
+        let mut reader = EventReader::new(b"a:1:{x}".as_slice());
+        assert_eq!(reader.next_event().ok(), Some(Some(Event::StartArray(1))));
+        assert!(reader.next_event().is_err());
+        assert_eq!(reader.next_event().ok(), Some(None));
+    }
+
+    #[test]
+    fn read_within_limits() {
+        let options = DecodeOptions::new()
+            .with_max_depth(1)
+            .with_max_container_len(2)
+            .with_max_total_elements(3)
+            .with_max_string_len(3);
+        assert!(events(b"a:2:{i:0;s:3:\"abc\";i:1;a:0:{}}", &options).is_err());
+        assert!(events(b"a:2:{i:0;s:3:\"abc\";i:1;N;}", &options).is_ok());
+        assert!(matches!(
+            events(b"s:4:\"abcd\";", &options),
+            Err(Error::LimitExceeded {
+                offset: 2,
+                limit: LimitExceeded::StringLength {
+                    declared: 4,
+                    max: 3
+                }
+            })
+        ));
+        assert!(matches!(
+            events(b"a:3:{}", &options),
+            Err(Error::LimitExceeded {
+                limit: LimitExceeded::ContainerLength { .. },
+                ..
+            })
+        ));
+        assert!(matches!(
+            events(b"a:1:{i:0;a:0:{}}", &options),
+            Err(Error::DepthExceeded { offset: 9, max: 1 })
+        ));
+
+        let options = options.with_max_depth(2);
+        assert!(matches!(
+            events(b"a:2:{i:0;a:2:{", &options),
+            Err(Error::LimitExceeded {
+                limit: LimitExceeded::TotalElements { max: 3 },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn read_numeric_string_keys() {
+        let input = b"a:2:{s:2:\"12\";N;s:2:\"01\";N;}";
+        let options = DecodeOptions::new().with_numeric_keys(NumericKeys::Canonicalize);
+        assert_eq!(
+            events(input, &options).ok(),
+            Some(vec![
+                Event::StartArray(2),
+                Event::Key(ArrayKey::Integer(12)),
+                Event::Scalar(Value::Null),
+                Event::Key(ArrayKey::String(b"01".into())),
+                Event::Scalar(Value::Null),
+                Event::EndArray,
+            ])
+        );
+        let options = DecodeOptions::new().with_numeric_keys(NumericKeys::Reject);
+        assert!(matches!(
+            events(input, &options),
+            Err(Error::NumericStringKey { offset: 5, key: 12 })
+        ));
+    }
+}
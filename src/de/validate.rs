@@ -0,0 +1,272 @@
+use std::{borrow::Cow, collections::BTreeSet};
+
+use winnow::{
+    combinator::{delimited, peek, preceded},
+    error::StrContext,
+    stream::Stream,
+    token::any,
+    Parser, Result,
+};
+
+use super::{
+    any_value, array_key, container_size, decode, nested, object_property_name, raw, session,
+    DecodeOptions, Input,
+};
+use crate::{
+    error::Error,
+    value::{canonical_integer, ArrayKey, Value},
+};
+
+/// Summary of the input checked by [`validate`] or [`validate_session`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats<'s> {
+    /// Deepest nesting of arrays and objects, `0` when there are none.
+    pub max_depth: usize,
+    /// Number of values, those held by arrays, objects, and session entries included.
+    pub values: usize,
+    /// Number of elements declared by arrays and objects together.
+    pub elements: usize,
+    /// Classes of the objects, custom objects, and enums.
+    pub class_names: BTreeSet<&'s [u8]>,
+    /// Offset and integer value of the string array keys PHP would store as integers, like
+    /// `s:2:"42";`, see [`NumericKeys`](crate::NumericKeys).
+    pub numeric_string_keys: Vec<(usize, i64)>,
+}
+
+/// Consume a value without building arrays and objects, strings being skipped by their declared
+/// length.
+pub(super) fn validate_value<'s>(input: &mut Input<'s>, stats: &mut Stats<'s>) -> Result<()> {
+    stats.values += 1;
+    match peek(any).parse_next(input)? {
+        b'a' => nested(|input: &mut Input<'s>| validate_array(input, stats)).parse_next(input),
+        b'O' => nested(|input: &mut Input<'s>| validate_object(input, stats)).parse_next(input),
+        _ => {
+            if let Value::CustomObject {
+                class_name: Cow::Borrowed(class_name),
+                ..
+            }
+            | Value::Enum {
+                class_name: Cow::Borrowed(class_name),
+                ..
+            } = any_value.parse_next(input)?
+            {
+                stats.class_names.insert(class_name);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn validate_array<'s>(input: &mut Input<'s>, stats: &mut Stats<'s>) -> Result<()> {
+    stats.max_depth = stats.max_depth.max(input.state.depth);
+    // Smallest pair is `i:0;N;`.
+    let len = delimited(b"a:", container_size(6), b":{").parse_next(input)?;
+    stats.elements += len;
+    for _ in 0..len {
+        // Offsets are counted from the end until the input's length is known, see `locate_keys`.
+        let remaining = input.eof_offset();
+        let string = matches!(input.input.first(), Some(b's' | b'S'));
+        match array_key.parse_next(input)? {
+            ArrayKey::Integer(key) if string => stats.numeric_string_keys.push((remaining, key)),
+            ArrayKey::String(key) => {
+                if let Some(key) = canonical_integer(&key) {
+                    stats.numeric_string_keys.push((remaining, key));
+                }
+            }
+            ArrayKey::Integer(_) => {}
+        }
+        validate_value(input, stats)?;
+    }
+    b'}'.void().parse_next(input)
+}
+
+fn validate_object<'s>(input: &mut Input<'s>, stats: &mut Stats<'s>) -> Result<()> {
+    stats.max_depth = stats.max_depth.max(input.state.depth);
+    let class_name = preceded(
+        b"O:",
+        raw::sized_string.context(StrContext::Label("class name")),
+    )
+    .parse_next(input)?;
+    stats.class_names.insert(class_name);
+    // Smallest property is `s:0:"";N;`.
+    let len = delimited(b':', container_size(9), b":{")
+        .context(StrContext::Label("object properties"))
+        .parse_next(input)?;
+    stats.elements += len;
+    for _ in 0..len {
+        (|input: &mut Input<'s>| {
+            object_property_name.parse_next(input)?;
+            validate_value(input, stats)
+        })
+        .context(StrContext::Label("object property"))
+        .parse_next(input)?;
+    }
+    b'}'.void().parse_next(input)
+}
+
+/// Turn the offsets of numeric string keys, counted from the end of `input`, into offsets from its
+/// start.
+fn locate_keys<'s>(mut stats: Stats<'s>, input: &[u8]) -> Stats<'s> {
+    for (offset, _) in &mut stats.numeric_string_keys {
+        *offset = input.len() - *offset;
+    }
+    stats
+}
+
+/// Check that input is a well-formed PHP serialized value, without decoding it.
+///
+/// ```
+/// let stats = muesli::validate(b"a:2:{i:0;O:3:\"Foo\":0:{}i:1;E:7:\"Bar:Baz\";}").unwrap();
+/// assert_eq!(stats.max_depth, 2);
+/// assert_eq!(stats.values, 3);
+/// assert_eq!(stats.elements, 2);
+/// assert_eq!(stats.class_names.into_iter().collect::<Vec<_>>(), [b"Bar", b"Foo"]);
+/// ```
+///
+/// # Errors
+///
+/// Will return `Err` if input is not a valid PHP serialize value.
+pub fn validate(input: &[u8]) -> Result<Stats<'_>, Error> {
+    validate_with(input, &DecodeOptions::default())
+}
+
+/// Check that input is a well-formed PHP serialized value, using the given options.
+///
+/// # Errors
+///
+/// Will return `Err` if input is not a valid PHP serialize value, or if it exceeds the limits set
+/// by `options`.
+pub fn validate_with<'s>(input: &'s [u8], options: &DecodeOptions) -> Result<Stats<'s>, Error> {
+    let mut stats = Stats::default();
+    decode(input, options, |input: &mut Input<'s>| {
+        validate_value(input, &mut stats)
+    })?;
+    Ok(locate_keys(stats, input))
+}
+
+/// Check that input is a well-formed PHP session, without decoding it.
+///
+/// # Errors
+///
+/// Will return `Err` if input is not a valid PHP session.
+pub fn validate_session(input: &[u8]) -> Result<Stats<'_>, Error> {
+    validate_session_with(input, &DecodeOptions::default())
+}
+
+/// Check that input is a well-formed PHP session, using the given options.
+///
+/// # Errors
+///
+/// Will return `Err` if input is not a valid PHP session, or if it exceeds the limits set by
+/// `options`.
+pub fn validate_session_with<'s>(
+    input: &'s [u8],
+    options: &DecodeOptions,
+) -> Result<Stats<'s>, Error> {
+    let mut stats = Stats::default();
+    session(input, options, |input: &mut Input<'s>| {
+        validate_value(input, &mut stats)
+    })?;
+    Ok(locate_keys(stats, input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NumericKeys, SessionFormat};
+
+    #[test]
+    fn validate_values() {
+        let stats = validate(
+            b"a:3:{i:0;O:3:\"Foo\":1:{s:4:\"\0*\0x\";a:1:{s:1:\"y\";C:3:\"Baz\":0:{}}}i:1;d:0.5;i:2;R:2;}",
+        )
+        .unwrap();
+        assert_eq!(
+            stats,
+            Stats {
+                max_depth: 3,
+                values: 6,
+                elements: 5,
+                class_names: [b"Baz".as_slice(), b"Foo"].into(),
+                numeric_string_keys: Vec::new(),
+            }
+        );
+        assert_eq!(
+            validate(b"i:1;").unwrap(),
+            Stats {
+                values: 1,
+                ..Stats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn validate_sessions() {
+        let stats = validate_session(b"a|a:1:{i:0;N;}!b|c|E:7:\"Foo:Bar\";").unwrap();
+        assert_eq!(
+            stats,
+            Stats {
+                max_depth: 1,
+                values: 3,
+                elements: 1,
+                class_names: [b"Foo".as_slice()].into(),
+                numeric_string_keys: Vec::new(),
+            }
+        );
+
+        let options = DecodeOptions::new().with_session_format(SessionFormat::PhpSerialize);
+        let stats =
+            validate_session_with(b"a:2:{s:1:\"a\";i:1;s:1:\"b\";a:0:{}}", &options).unwrap();
+        assert_eq!((stats.max_depth, stats.values, stats.elements), (1, 2, 0));
+    }
+
+    #[test]
+    fn validate_numeric_string_keys() {
+        let input = b"a:3:{s:2:\"42\";N;s:2:\"07\";a:1:{s:2:\"-1\";N;}i:3;N;}";
+        assert_eq!(
+            validate(input).unwrap().numeric_string_keys,
+            [(5, 42), (30, -1)]
+        );
+        let options = DecodeOptions::new().with_numeric_keys(NumericKeys::Canonicalize);
+        assert_eq!(
+            validate_with(input, &options).unwrap().numeric_string_keys,
+            [(5, 42), (30, -1)]
+        );
+        assert_eq!(
+            validate_session(b"a|a:1:{s:1:\"7\";N;}")
+                .unwrap()
+                .numeric_string_keys,
+            [(7, 7)]
+        );
+    }
+
+    #[test]
+    fn reject_invalid_input() {
+        assert!(matches!(
+            validate(b"a:1:{i:0;N;N;}"),
+            Err(Error::InvalidToken { offset: 11, .. })
+        ));
+        assert!(matches!(
+            validate(b"O:3:\"Foo\":1:{s:1:\"x\";i:1;"),
+            Err(Error::UnexpectedEof { offset: 25, .. })
+        ));
+        assert!(matches!(
+            validate(b"N;N;"),
+            Err(Error::TrailingData { offset: 2 })
+        ));
+        assert!(matches!(
+            validate_with(b"a:1:{i:0;a:0:{}}", &DecodeOptions::new().with_max_depth(1)),
+            Err(Error::DepthExceeded { offset: 9, max: 1 })
+        ));
+        assert!(matches!(
+            validate_session(b"a|N;b"),
+            Err(Error::InvalidToken { offset: 4, .. })
+        ));
+        let options = DecodeOptions::new().with_numeric_keys(NumericKeys::Reject);
+        assert!(validate_with(b"a:1:{s:2:\"07\";N;}", &options).is_ok());
+        assert!(matches!(
+            validate_with(b"a:1:{s:1:\"7\";N;}", &options),
+            Err(Error::NumericStringKey { offset: 5, key: 7 })
+        ));
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    fmt::{self, Write as _},
+    io,
+};
+
+/// Errors raised while decoding or encoding PHP serialized data.
+///
+/// Decoding errors carry the byte `offset` at which decoding stopped, [`Error::render`] shows it
+/// within the input.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Input ended while a value was still being decoded.
+    UnexpectedEof {
+        offset: usize,
+        expected: Option<&'static str>,
+    },
+    /// Input contains a token that isn't valid at `offset`.
+    InvalidToken {
+        offset: usize,
+        expected: Option<&'static str>,
+    },
+    /// A string doesn't end after its `declared` length.
+    LengthMismatch { offset: usize, declared: usize },
+    /// Arrays and objects are nested deeper than `max`.
+    DepthExceeded { offset: usize, max: usize },
+    /// Input exceeds one of the limits set by [`DecodeOptions`](crate::DecodeOptions).
+    LimitExceeded { offset: usize, limit: LimitExceeded },
+    /// A string array key holds an integer PHP would store as `key`, see
+    /// [`NumericKeys::Reject`](crate::NumericKeys::Reject).
+    NumericStringKey { offset: usize, key: i64 },
+    /// A complete value was decoded but input continues.
+    TrailingData { offset: usize },
+    /// `R:` or `r:` at `path` points to slot `index`, which no value has.
+    DanglingReference { path: String, index: usize },
+    /// `R:` or `r:` at `path` points to slot `index`, which comes after it.
+    ForwardReference { path: String, index: usize },
+    /// `r:` at `path` points to slot `index`, which isn't an object.
+    NotAnObject { path: String, index: usize },
+    /// [`Graph`](crate::Graph) can't be encoded because of the node with this id.
+    InvalidGraph { node: usize, reason: &'static str },
+    /// [`Query`](crate::Query) can't be read because of the character at `position`.
+    InvalidQuery {
+        position: usize,
+        reason: &'static str,
+    },
+    /// Session key can't be written in the selected session format.
+    InvalidSessionKey { key: Vec<u8> },
+    /// Writing encoded data failed.
+    Io(io::Error),
+    /// Error raised by a serde implementation.
+    Message(String),
+}
+
+/// Limit exceeded by the input, see [`Error::LimitExceeded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// An array or object declares more than `max` elements.
+    ContainerLength { declared: usize, max: usize },
+    /// Arrays and objects declare more than `max` elements in total.
+    TotalElements { max: usize },
+    /// A string declares a length over `max`.
+    StringLength { declared: usize, max: usize },
+    /// A size is declared that the `remaining` bytes of input can't possibly hold.
+    RemainingInput { declared: usize, remaining: usize },
+}
+
+impl Error {
+    /// Byte offset of the input at which decoding stopped.
+    #[must_use]
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Error::UnexpectedEof { offset, .. }
+            | Error::InvalidToken { offset, .. }
+            | Error::LengthMismatch { offset, .. }
+            | Error::DepthExceeded { offset, .. }
+            | Error::LimitExceeded { offset, .. }
+            | Error::NumericStringKey { offset, .. }
+            | Error::TrailingData { offset } => Some(*offset),
+            Error::DanglingReference { .. }
+            | Error::ForwardReference { .. }
+            | Error::NotAnObject { .. }
+            | Error::InvalidGraph { .. }
+            | Error::InvalidQuery { .. }
+            | Error::InvalidSessionKey { .. }
+            | Error::Io(_)
+            | Error::Message(_) => None,
+        }
+    }
+
+    /// Move the offset of a decoding error by `by` bytes, for input that was a region of a larger
+    /// one.
+    pub(crate) fn shift_offset(mut self, by: usize) -> Self {
+        match &mut self {
+            Error::UnexpectedEof { offset, .. }
+            | Error::InvalidToken { offset, .. }
+            | Error::LengthMismatch { offset, .. }
+            | Error::DepthExceeded { offset, .. }
+            | Error::LimitExceeded { offset, .. }
+            | Error::NumericStringKey { offset, .. }
+            | Error::TrailingData { offset } => *offset += by,
+            _ => {}
+        }
+        self
+    }
+
+    /// Describe the error along with the region of `input` where decoding stopped.
+    ///
+    /// ```
+    /// let input = b"a:1:{i:0;x:1;}";
+    /// let err = muesli::unserialize(input).unwrap_err();
+    /// assert_eq!(
+    ///     err.render(input),
+    ///     "invalid token at offset 9, expected value type\n  | a:1:{i:0;x:1;}\n  |          ^",
+    /// );
+    /// ```
+    #[must_use]
+    pub fn render(&self, input: &[u8]) -> String {
+        const CONTEXT: usize = 24;
+
+        let mut rendered = self.to_string();
+        if let Some(offset) = self.offset() {
+            let offset = offset.min(input.len());
+            let start = offset.saturating_sub(CONTEXT);
+            let end = offset.saturating_add(CONTEXT).min(input.len());
+            let mut before = escape(&input[start..offset]);
+            if start > 0 {
+                before.insert_str(0, "...");
+            }
+            let after = escape(&input[offset..end]);
+            let ellipsis = if end < input.len() { "..." } else { "" };
+            let padding = " ".repeat(before.chars().count());
+            let _ = write!(rendered, "\n  | {before}{after}{ellipsis}\n  | {padding}^");
+        }
+        rendered
+    }
+}
+
+/// Printable ASCII as is, other bytes as `\xNN`.
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        if (b' '..=b'~').contains(&byte) {
+            escaped.push(char::from(byte));
+        } else {
+            let _ = write!(escaped, "\\x{byte:02x}");
+        }
+    }
+    escaped
+}
+
+fn write_expected(f: &mut fmt::Formatter<'_>, expected: Option<&str>) -> fmt::Result {
+    match expected {
+        Some(expected) => write!(f, ", expected {expected}"),
+        None => Ok(()),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnexpectedEof { offset, expected } => {
+                write!(f, "unexpected end of input at offset {offset}")?;
+                write_expected(f, *expected)
+            }
+            Error::InvalidToken { offset, expected } => {
+                write!(f, "invalid token at offset {offset}")?;
+                write_expected(f, *expected)
+            }
+            Error::LengthMismatch { offset, declared } => write!(
+                f,
+                "string doesn't end after its declared length of {declared} at offset {offset}"
+            ),
+            Error::DepthExceeded { offset, max } => {
+                write!(f, "maximum depth of {max} exceeded at offset {offset}")
+            }
+            Error::LimitExceeded { offset, limit } => write!(f, "{limit} at offset {offset}"),
+            Error::NumericStringKey { offset, key } => write!(
+                f,
+                "string array key \"{key}\" should be the integer {key} at offset {offset}"
+            ),
+            Error::TrailingData { offset } => {
+                write!(f, "unexpected data after value at offset {offset}")
+            }
+            Error::DanglingReference { path, index } => {
+                write!(f, "reference at \"{path}\" points to missing slot {index}")
+            }
+            Error::ForwardReference { path, index } => {
+                write!(
+                    f,
+                    "reference at \"{path}\" points to slot {index}, which comes after it"
+                )
+            }
+            Error::NotAnObject { path, index } => write!(
+                f,
+                "object reference at \"{path}\" points to slot {index}, which isn't an object"
+            ),
+            Error::InvalidGraph { node, reason } => {
+                write!(f, "invalid graph at node {node}: {reason}")
+            }
+            Error::InvalidQuery { position, reason } => {
+                write!(f, "invalid query at position {position}: {reason}")
+            }
+            Error::InvalidSessionKey { key } => write!(
+                f,
+                "session key \"{}\" can't be written in this session format",
+                escape(key)
+            ),
+            Error::Io(err) => write!(f, "write failed: {err}"),
+            Error::Message(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::ContainerLength { declared, max } => write!(
+                f,
+                "container declares {declared} elements, maximum is {max}"
+            ),
+            LimitExceeded::TotalElements { max } => {
+                write!(f, "maximum total of {max} elements exceeded")
+            }
+            LimitExceeded::StringLength { declared, max } => {
+                write!(f, "string declares {declared} bytes, maximum is {max}")
+            }
+            LimitExceeded::RemainingInput {
+                declared,
+                remaining,
+            } => write!(
+                f,
+                "declared size {declared} exceeds the {remaining} remaining bytes of input"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_error() {
+        let input = b"a:1:{s:3:\"foo\";s:2:\"\xffbar\";}";
+        let err = crate::unserialize(input).unwrap_err();
+        assert_eq!(
+            err.render(input),
+            "string doesn't end after its declared length of 2 at offset 22\n  \
+             | a:1:{s:3:\"foo\";s:2:\"\\xffbar\";}\n  \
+             |                          ^"
+        );
+
+        let input = [b"s:80:\"".as_slice(), &[b'x'; 80], b"\";"].concat();
+        let err = Error::TrailingData { offset: 40 };
+        assert_eq!(
+            err.render(&input),
+            format!(
+                "unexpected data after value at offset 40\n  | ...{}...\n  | {}^",
+                "x".repeat(48),
+                " ".repeat(27)
+            )
+        );
+
+        let err = Error::Message("invalid length".to_string());
+        assert_eq!(err.render(b"N;"), "invalid length");
+    }
+}
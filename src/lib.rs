@@ -25,16 +25,16 @@
 //!
 //! let data = vec![
 //!     SessionEntry {
-//!         key: b"foo",
-//!         value: Value::Integer(42),
+//!         key: b"foo".into(),
+//!         value: Some(Value::Integer(42)),
 //!     },
 //!     SessionEntry {
-//!         key: b"bar",
-//!         value: Value::String(b"baz|qux".as_slice()),
+//!         key: b"bar".into(),
+//!         value: Some(Value::String(b"baz|qux".into())),
 //!     },
 //!     SessionEntry {
-//!         key: b"pub",
-//!         value: Value::Integer(1337),
+//!         key: b"pub".into(),
+//!         value: Some(Value::Integer(1337)),
 //!     },
 //! ];
 //!
@@ -43,15 +43,40 @@
 //! assert_eq!(session, b"foo|i:42;bar|s:7:\"baz|qux\";pub|i:1337;".as_slice());
 //! ```
 //!
+//! ## Features
+//!
+//! - `serde`: deserialize PHP serialized values into Rust types implementing `serde::Deserialize`
+//!   with `from_bytes()` and `from_value()`, and serialize Rust types implementing
+//!   `serde::Serialize` with `to_writer()`, `to_vec()`, or a configured `Serializer`.
+//!
 //! [php-serialize]: https://www.php.net/manual/en/function.serialize.php
 //! [php-unserialize]: https://www.php.net/manual/en/function.unserialize.php
 //! [php-session-encode]: https://www.php.net/manual/en/function.session-encode.php
 //! [php-session-decode]: https://www.php.net/manual/en/function.session-decode.php
 
 mod de;
+mod error;
+mod macros;
+mod path;
+mod query;
+mod reference;
 mod ser;
 pub mod value;
 
-pub use de::{session_decode, unserialize};
-pub use ser::{serialize, session_encode};
+pub use de::lazy::{LazyEntry, LazySession};
+#[cfg(feature = "serde")]
+pub use de::serde::{from_bytes, from_value};
+pub use de::stream::{Event, EventReader};
+pub use de::validate::{validate, validate_session, validate_session_with, validate_with, Stats};
+pub use de::{session_decode, session_decode_with, unserialize, unserialize_with, DecodeOptions};
+pub use error::{Error, LimitExceeded};
+pub use path::{Path, PathSegment};
+pub use query::{query_session, Match, Query, Selector};
+pub use reference::{Graph, GraphEntry, Node, NodeId, NodeProperty, Slot, SlotTable};
+#[cfg(feature = "serde")]
+pub use ser::serde::{to_vec, to_writer, Serializer};
+pub use ser::{
+    serialize, serialize_graph, serialize_with, serialized_len, serialized_len_with,
+    session_encode, session_encode_graph, session_encode_with, EncodeOptions,
+};
 pub use value::*;
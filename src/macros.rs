@@ -0,0 +1,267 @@
+/// Build a [`Value`](crate::Value) with PHP's syntax.
+///
+/// Arrays are written `[key => value, value]`, elements without a key getting the next integer
+/// key as in PHP. Objects are written `new Class { public a: 1, protected b: 2, private c: 3 }`,
+/// the class and property names being identifiers or string literals, e.g. `new "App\\User" {}`.
+/// `null` is `null`, and any other expression is converted with [`From`].
+///
+/// ```
+/// use muesli::{php, serialize};
+///
+/// let value = php!([
+///     "theme" => "dark",
+///     0 => 1.5,
+///     "tags" => ["a", "b"],
+///     "obj" => new Foo { public x: 1, private y: null },
+/// ]);
+///
+/// let mut output = Vec::new();
+/// serialize(&mut output, &value).unwrap();
+/// assert_eq!(
+///     output,
+///     b"a:4:{s:5:\"theme\";s:4:\"dark\";i:0;d:1.5;s:4:\"tags\";a:2:{i:0;s:1:\"a\";i:1;s:1:\"b\";}\
+///       s:3:\"obj\";O:3:\"Foo\":2:{s:1:\"x\";i:1;s:6:\"\0Foo\0y\";N;}}"
+/// );
+/// ```
+#[macro_export]
+macro_rules! php {
+    (null) => {
+        $crate::Value::Null
+    };
+    ([ $($elements:tt)* ]) => {
+        $crate::value::php_array($crate::php!(@elements [] $($elements)*))
+    };
+    (new $class:tt { $($properties:tt)* }) => {
+        $crate::Value::Object {
+            class_name: ::std::borrow::Cow::Borrowed($crate::php!(@name $class).as_bytes()),
+            properties: $crate::php!(@properties $class [] $($properties)*),
+        }
+    };
+    ($value:expr) => {
+        $crate::Value::from($value)
+    };
+
+    (@name $name:ident) => {
+        stringify!($name)
+    };
+    (@name $name:literal) => {
+        $name
+    };
+
+    // Elements of an array, accumulated as `(key, value),` until the closing bracket.
+    (@elements [$($out:tt)*]) => {
+        vec![$($out)*]
+    };
+    (@elements [$($out:tt)*] null $(, $($rest:tt)*)?) => {
+        $crate::php!(@elements [$($out)* (None, $crate::Value::Null),] $($($rest)*)?)
+    };
+    (@elements [$($out:tt)*] [ $($array:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::php!(@elements [$($out)* (None, $crate::php!([$($array)*])),] $($($rest)*)?)
+    };
+    (@elements [$($out:tt)*] new $class:tt { $($object:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::php!(
+            @elements [$($out)* (None, $crate::php!(new $class { $($object)* })),] $($($rest)*)?
+        )
+    };
+    (@elements [$($out:tt)*] $key:expr => null $(, $($rest:tt)*)?) => {
+        $crate::php!(
+            @elements [$($out)* (Some($crate::ArrayKey::from($key)), $crate::Value::Null),]
+            $($($rest)*)?
+        )
+    };
+    (@elements [$($out:tt)*] $key:expr => [ $($array:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::php!(
+            @elements [$($out)* (Some($crate::ArrayKey::from($key)), $crate::php!([$($array)*])),]
+            $($($rest)*)?
+        )
+    };
+    (
+        @elements [$($out:tt)*] $key:expr => new $class:tt { $($object:tt)* }
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::php!(
+            @elements [
+                $($out)*
+                (Some($crate::ArrayKey::from($key)), $crate::php!(new $class { $($object)* })),
+            ]
+            $($($rest)*)?
+        )
+    };
+    (@elements [$($out:tt)*] $key:expr => $value:expr $(, $($rest:tt)*)?) => {
+        $crate::php!(
+            @elements [$($out)* (Some($crate::ArrayKey::from($key)), $crate::Value::from($value)),]
+            $($($rest)*)?
+        )
+    };
+    (@elements [$($out:tt)*] $value:expr $(, $($rest:tt)*)?) => {
+        $crate::php!(@elements [$($out)* (None, $crate::Value::from($value)),] $($($rest)*)?)
+    };
+
+    // Properties of an object of class `$class`, accumulated until the closing brace.
+    (@properties $class:tt [$($out:tt)*]) => {
+        vec![$($out)*]
+    };
+    (@properties $class:tt [$($out:tt)*] $visibility:ident $name:tt : null $(, $($rest:tt)*)?) => {
+        $crate::php!(
+            @properties $class [
+                $($out)* $crate::php!(@property $class $visibility $name $crate::Value::Null),
+            ]
+            $($($rest)*)?
+        )
+    };
+    (
+        @properties $class:tt [$($out:tt)*] $visibility:ident $name:tt : [ $($array:tt)* ]
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::php!(
+            @properties $class [
+                $($out)*
+                $crate::php!(@property $class $visibility $name $crate::php!([$($array)*])),
+            ]
+            $($($rest)*)?
+        )
+    };
+    (
+        @properties $class:tt [$($out:tt)*] $visibility:ident $name:tt :
+        new $object_class:tt { $($object:tt)* } $(, $($rest:tt)*)?
+    ) => {
+        $crate::php!(
+            @properties $class [
+                $($out)*
+                $crate::php!(
+                    @property $class $visibility $name
+                    $crate::php!(new $object_class { $($object)* })
+                ),
+            ]
+            $($($rest)*)?
+        )
+    };
+    (
+        @properties $class:tt [$($out:tt)*] $visibility:ident $name:tt : $value:expr
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::php!(
+            @properties $class [
+                $($out)*
+                $crate::php!(@property $class $visibility $name $crate::Value::from($value)),
+            ]
+            $($($rest)*)?
+        )
+    };
+
+    (@property $class:tt $visibility:ident $name:tt $value:expr) => {
+        $crate::ObjectProperty {
+            visibility: $crate::php!(@visibility $class $visibility),
+            name: ::std::borrow::Cow::Borrowed($crate::php!(@name $name).as_bytes()),
+            value: $value,
+        }
+    };
+    (@visibility $class:tt public) => {
+        $crate::ObjectPropertyVisibility::Public
+    };
+    (@visibility $class:tt protected) => {
+        $crate::ObjectPropertyVisibility::Protected
+    };
+    (@visibility $class:tt private) => {
+        $crate::ObjectPropertyVisibility::Private(::std::borrow::Cow::Borrowed(
+            $crate::php!(@name $class).as_bytes(),
+        ))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::{ArrayKey, ObjectProperty, ObjectPropertyVisibility, Value};
+
+    #[test]
+    fn build_scalars() {
+        assert_eq!(php!(null), Value::Null);
+        assert_eq!(php!(true), Value::Boolean(true));
+        assert_eq!(php!(-1), Value::Integer(-1));
+        assert_eq!(php!(1.5), Value::Decimal(1.5));
+        assert_eq!(php!("a"), Value::String(b"a".into()));
+        assert_eq!(php!(None::<i64>), Value::Null);
+        let name = String::from("b");
+        assert_eq!(php!(name), Value::String(b"b".into()));
+    }
+
+    #[test]
+    fn build_arrays() {
+        assert_eq!(php!([]), Value::Array(vec![]));
+        assert_eq!(
+            php!(["a", 5 => "b", "c", "7" => null, "07" => [1], -1 => 2, "d"]),
+            Value::Array(vec![
+                (ArrayKey::Integer(0), Value::String(b"a".into())),
+                (ArrayKey::Integer(5), Value::String(b"b".into())),
+                (ArrayKey::Integer(6), Value::String(b"c".into())),
+                (ArrayKey::Integer(7), Value::Null),
+                (
+                    ArrayKey::String(b"07".into()),
+                    Value::Array(vec![(ArrayKey::Integer(0), Value::Integer(1))]),
+                ),
+                (ArrayKey::Integer(-1), Value::Integer(2)),
+                (ArrayKey::Integer(8), Value::String(b"d".into())),
+            ])
+        );
+        // A key given again replaces the value in place.
+        assert_eq!(
+            php!([0 => "a", "b", "0" => "c", 1 + 1 => 2 * 2]),
+            Value::Array(vec![
+                (ArrayKey::Integer(0), Value::String(b"c".into())),
+                (ArrayKey::Integer(1), Value::String(b"b".into())),
+                (ArrayKey::Integer(2), Value::Integer(4)),
+            ])
+        );
+        assert_eq!(php!([-5 => 1, 2]).get_index(-4), Some(&Value::Integer(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "already occupied")]
+    fn build_array_past_max_key() {
+        let _ = php!([i64::MAX => 1, 2]);
+    }
+
+    #[test]
+    fn build_objects() {
+        let property = |visibility, name: &'static [u8], value| ObjectProperty {
+            visibility,
+            name: name.into(),
+            value,
+        };
+        assert_eq!(
+            php!(new "App\\User" {
+                public name: "x",
+                protected "list": [new stdClass {}],
+                private secret: null,
+            }),
+            Value::Object {
+                class_name: b"App\\User".into(),
+                properties: vec![
+                    property(
+                        ObjectPropertyVisibility::Public,
+                        b"name",
+                        Value::String(b"x".into())
+                    ),
+                    property(
+                        ObjectPropertyVisibility::Protected,
+                        b"list",
+                        Value::Array(vec![(
+                            ArrayKey::Integer(0),
+                            Value::Object {
+                                class_name: b"stdClass".into(),
+                                properties: vec![],
+                            }
+                        )])
+                    ),
+                    property(
+                        ObjectPropertyVisibility::Private(Cow::Borrowed(b"App\\User")),
+                        b"secret",
+                        Value::Null
+                    ),
+                ],
+            }
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::{borrow::Cow, fmt};
+
+use crate::value::ArrayKey;
+
+/// Step from a value to one of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment<'a> {
+    /// Value of the session entry with this key.
+    Entry(Cow<'a, [u8]>),
+    /// Element of an array.
+    Key(ArrayKey<'a>),
+    /// Property of an object, by name without its visibility mangling.
+    Property(Cow<'a, [u8]>),
+}
+
+/// Location of a value within a decoded value or session.
+///
+/// Displayed like a JSON pointer, e.g. `/cart/items/0/sku`, the root being the empty path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Path<'a>(Vec<PathSegment<'a>>);
+
+impl<'a> Path<'a> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn segments(&self) -> &[PathSegment<'a>] {
+        &self.0
+    }
+
+    pub(crate) fn push(&mut self, segment: PathSegment<'a>) {
+        self.0.push(segment);
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.0.pop();
+    }
+}
+
+fn write_segment(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    let segment = String::from_utf8_lossy(bytes);
+    f.write_str(&segment.replace('~', "~0").replace('/', "~1"))
+}
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.0 {
+            f.write_str("/")?;
+            match segment {
+                PathSegment::Key(ArrayKey::Integer(n)) => write!(f, "{n}")?,
+                PathSegment::Entry(bytes)
+                | PathSegment::Key(ArrayKey::String(bytes))
+                | PathSegment::Property(bytes) => write_segment(f, bytes)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_path() {
+        let mut path = Path::new();
+        assert_eq!(path.to_string(), "");
+
+        path.push(PathSegment::Entry(b"cart".into()));
+        path.push(PathSegment::Property(b"items".into()));
+        path.push(PathSegment::Key(ArrayKey::Integer(-1)));
+        path.push(PathSegment::Key(ArrayKey::String(b"a/b~c".into())));
+        assert_eq!(path.to_string(), "/cart/items/-1/a~1b~0c");
+
+        path.pop();
+        assert_eq!(path.to_string(), "/cart/items/-1");
+    }
+}
@@ -0,0 +1,376 @@
+use std::{borrow::Cow, collections::HashSet, ptr};
+
+use crate::{
+    error::Error,
+    path::{Path, PathSegment},
+    value::{canonical_integer, ArrayKey, SessionEntry, Value},
+};
+
+/// Step of a [`Query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector<'q> {
+    /// Child with this name: session entry key, array key, or property name without its
+    /// visibility mangling. Numeric names also match integer array keys.
+    Name(Cow<'q, [u8]>),
+    /// Every child.
+    Wildcard,
+    /// The value itself and all the values nested in it, at any depth.
+    Descendants,
+}
+
+/// Selection of values by path, like a JSON pointer with wildcards.
+///
+/// Segments are separated by `/` with `~1` standing for `/` and `~0` for `~`, as in JSON
+/// pointers. `*` selects every child, and an empty segment, as in `//email`, recursively selects
+/// every nested value, so neither addresses keys that are empty or `*`.
+///
+/// ```
+/// use muesli::{unserialize, Query};
+///
+/// let value = unserialize(
+///     b"a:1:{s:4:\"cart\";O:4:\"Cart\":1:{s:11:\"\0Cart\0items\";a:2:{\
+///       i:0;a:1:{s:3:\"sku\";s:4:\"A-42\";}i:1;a:1:{s:3:\"sku\";s:3:\"B-7\";}}}}",
+/// )
+/// .unwrap();
+///
+/// let query = Query::parse("/cart/items/*/sku").unwrap();
+/// let skus: Vec<_> = query
+///     .select(&value)
+///     .iter()
+///     .map(|found| (found.path.to_string(), found.value.as_str().unwrap()))
+///     .collect();
+/// assert_eq!(
+///     skus,
+///     [
+///         ("/cart/items/0/sku".to_string(), "A-42"),
+///         ("/cart/items/1/sku".to_string(), "B-7")
+///     ]
+/// );
+///
+/// assert_eq!(value.query("//sku").unwrap().len(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query<'q>(Vec<Selector<'q>>);
+
+/// Value selected by a [`Query`], along with its location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'v> {
+    pub path: Path<'v>,
+    pub value: &'v Value<'v>,
+}
+
+fn invalid(position: usize, reason: &'static str) -> Error {
+    Error::InvalidQuery { position, reason }
+}
+
+/// Decode the `~0` and `~1` escapes of a segment starting at `position` in the query.
+fn unescape(segment: &str, position: usize) -> Result<Cow<'_, [u8]>, Error> {
+    if !segment.contains('~') {
+        return Ok(Cow::Borrowed(segment.as_bytes()));
+    }
+    let mut name = Vec::with_capacity(segment.len());
+    let mut bytes = segment.bytes().enumerate();
+    while let Some((offset, byte)) = bytes.next() {
+        if byte != b'~' {
+            name.push(byte);
+            continue;
+        }
+        name.push(match bytes.next() {
+            Some((_, b'0')) => b'~',
+            Some((_, b'1')) => b'/',
+            _ => return Err(invalid(position + offset, "`~` must be followed by 0 or 1")),
+        });
+    }
+    Ok(Cow::Owned(name))
+}
+
+/// Value or session whose children are being selected.
+#[derive(Clone, Copy)]
+enum Node<'v> {
+    Session(&'v [SessionEntry<'v>]),
+    Value(&'v Value<'v>),
+}
+
+type Found<'v> = (Path<'v>, Node<'v>);
+
+fn children(node: Node<'_>) -> Vec<(PathSegment<'_>, &Value<'_>)> {
+    match node {
+        Node::Session(entries) => entries
+            .iter()
+            .filter_map(|entry| {
+                let value = entry.value.as_ref()?;
+                Some((PathSegment::Entry(Cow::Borrowed(&entry.key)), value))
+            })
+            .collect(),
+        Node::Value(Value::Array(items)) => items
+            .iter()
+            .map(|(key, value)| (PathSegment::Key(key.as_borrowed()), value))
+            .collect(),
+        Node::Value(Value::Object { properties, .. }) => properties
+            .iter()
+            .map(|property| {
+                (
+                    PathSegment::Property(Cow::Borrowed(&property.name)),
+                    &property.value,
+                )
+            })
+            .collect(),
+        Node::Value(_) => Vec::new(),
+    }
+}
+
+fn has_name(segment: &PathSegment, name: &[u8]) -> bool {
+    match segment {
+        PathSegment::Entry(key)
+        | PathSegment::Property(key)
+        | PathSegment::Key(ArrayKey::String(key)) => **key == *name,
+        PathSegment::Key(ArrayKey::Integer(n)) => canonical_integer(name) == Some(*n),
+    }
+}
+
+/// Add `node` and everything nested in it to `found`, parents first, unless `seen` shows it was
+/// already added along with an ancestor.
+fn descendants<'v>(
+    path: &mut Path<'v>,
+    node: Node<'v>,
+    found: &mut Vec<Found<'v>>,
+    seen: &mut HashSet<*const Value<'v>>,
+) {
+    if let Node::Value(value) = node {
+        if !seen.insert(ptr::from_ref(value)) {
+            return;
+        }
+    }
+    found.push((path.clone(), node));
+    for (segment, value) in children(node) {
+        path.push(segment);
+        descendants(path, Node::Value(value), found, seen);
+        path.pop();
+    }
+}
+
+impl<'q> Query<'q> {
+    /// Read a query like `/cart/items/*/sku`, the empty query selecting the root.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the query doesn't start with `/`, or if a `~` isn't followed by `0` or
+    /// `1`.
+    pub fn parse(query: &'q str) -> Result<Self, Error> {
+        if query.is_empty() {
+            return Ok(Self::default());
+        }
+        let rest = query
+            .strip_prefix('/')
+            .ok_or_else(|| invalid(0, "query must start with `/`"))?;
+        let mut selectors = Vec::new();
+        let mut position = 1;
+        for segment in rest.split('/') {
+            let selector = match segment {
+                "" => Selector::Descendants,
+                "*" => Selector::Wildcard,
+                _ => Selector::Name(unescape(segment, position)?),
+            };
+            // Descending twice in a row would select the same values again.
+            if !(selector == Selector::Descendants && selectors.last() == Some(&selector)) {
+                selectors.push(selector);
+            }
+            position += segment.len() + 1;
+        }
+        Ok(Self(selectors))
+    }
+
+    #[must_use]
+    pub fn selectors(&self) -> &[Selector<'q>] {
+        &self.0
+    }
+
+    fn run<'v>(&self, root: Node<'v>) -> Vec<Match<'v>> {
+        let mut current = vec![(Path::new(), root)];
+        for selector in &self.0 {
+            let mut next = Vec::new();
+            // Ancestors come first, so nodes already reached from one aren't selected again.
+            let mut seen = HashSet::new();
+            for (mut path, node) in current {
+                match selector {
+                    Selector::Descendants => descendants(&mut path, node, &mut next, &mut seen),
+                    Selector::Wildcard | Selector::Name(_) => {
+                        for (segment, value) in children(node) {
+                            if let Selector::Name(name) = selector {
+                                if !has_name(&segment, name) {
+                                    continue;
+                                }
+                            }
+                            let mut path = path.clone();
+                            path.push(segment);
+                            next.push((path, Node::Value(value)));
+                        }
+                    }
+                }
+            }
+            current = next;
+        }
+        current
+            .into_iter()
+            .filter_map(|(path, node)| match node {
+                Node::Value(value) => Some(Match { path, value }),
+                Node::Session(_) => None,
+            })
+            .collect()
+    }
+
+    /// Values of `value` selected by the query, in the order they appear.
+    #[must_use]
+    pub fn select<'v>(&self, value: &'v Value<'v>) -> Vec<Match<'v>> {
+        self.run(Node::Value(value))
+    }
+
+    /// Values of a session selected by the query, its first segment naming entries.
+    ///
+    /// Undefined variables are never selected.
+    #[must_use]
+    pub fn select_session<'v>(&self, entries: &'v [SessionEntry<'v>]) -> Vec<Match<'v>> {
+        self.run(Node::Session(entries))
+    }
+}
+
+impl<'q> From<Vec<Selector<'q>>> for Query<'q> {
+    fn from(selectors: Vec<Selector<'q>>) -> Self {
+        Self(selectors)
+    }
+}
+
+impl Value<'_> {
+    /// Values selected by a query, see [`Query`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the query is invalid.
+    pub fn query<'v>(&'v self, query: &str) -> Result<Vec<Match<'v>>, Error> {
+        Ok(Query::parse(query)?.select(self))
+    }
+}
+
+/// Values of a session selected by a query, see [`Query`].
+///
+/// # Errors
+///
+/// Will return `Err` if the query is invalid.
+pub fn query_session<'v>(
+    entries: &'v [SessionEntry<'v>],
+    query: &str,
+) -> Result<Vec<Match<'v>>, Error> {
+    Ok(Query::parse(query)?.select_session(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{php, session_decode};
+
+    use super::*;
+
+    fn paths(matches: &[Match]) -> Vec<String> {
+        matches.iter().map(|found| found.path.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_queries() {
+        assert_eq!(Query::parse("").unwrap().selectors(), []);
+        assert_eq!(
+            Query::parse("/a~1b/*//~0x///").unwrap().selectors(),
+            [
+                Selector::Name(b"a/b".as_slice().into()),
+                Selector::Wildcard,
+                Selector::Descendants,
+                Selector::Name(b"~x".as_slice().into()),
+                Selector::Descendants,
+            ]
+        );
+        assert!(matches!(
+            Query::parse("cart"),
+            Err(Error::InvalidQuery { position: 0, .. })
+        ));
+        assert!(matches!(
+            Query::parse("/cart/a~2"),
+            Err(Error::InvalidQuery { position: 7, .. })
+        ));
+        assert!(matches!(
+            Query::parse("/a~"),
+            Err(Error::InvalidQuery { position: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn select_values() {
+        let value = php!([
+            "user" => new User {
+                private email: "a@example.com",
+                protected friends: [new User { public email: "b@example.com" }],
+            },
+            "tags" => ["x", "y"],
+            "7" => "seven",
+        ]);
+
+        let found = value.query("/user/email").unwrap();
+        assert_eq!(paths(&found), ["/user/email"]);
+        assert_eq!(found[0].value.as_str(), Some("a@example.com"));
+
+        assert_eq!(
+            paths(&value.query("//email").unwrap()),
+            ["/user/email", "/user/friends/0/email"]
+        );
+        assert_eq!(
+            paths(&value.query("/tags/*").unwrap()),
+            ["/tags/0", "/tags/1"]
+        );
+        assert_eq!(paths(&value.query("/tags/1").unwrap()), ["/tags/1"]);
+        assert_eq!(paths(&value.query("/7").unwrap()), ["/7"]);
+        assert_eq!(paths(&value.query("").unwrap()), [""]);
+        assert_eq!(value.query("/tags/1/x").unwrap(), []);
+        assert_eq!(value.query("/missing").unwrap(), []);
+        assert_eq!(value.query("/").unwrap().len(), 10);
+        assert_eq!(
+            paths(&value.query("/*//*").unwrap()),
+            [
+                "/user/email",
+                "/user/friends",
+                "/user/friends/0",
+                "/user/friends/0/email",
+                "/tags/0",
+                "/tags/1",
+            ]
+        );
+    }
+
+    #[test]
+    fn select_nested_descendants_once() {
+        let value =
+            crate::unserialize(b"a:1:{s:1:\"a\";a:1:{s:1:\"a\";a:1:{s:1:\"b\";i:1;}}}").unwrap();
+        assert_eq!(paths(&value.query("//a//b").unwrap()), ["/a/a/b"]);
+        assert_eq!(paths(&value.query("//a").unwrap()), ["/a", "/a/a"]);
+        assert_eq!(
+            paths(&value.query("//a//").unwrap()),
+            ["/a", "/a/a", "/a/a/b"]
+        );
+
+        let value = php!([
+            "a" => ["x" => ["a" => ["b" => 1]], "b" => 2],
+        ]);
+        assert_eq!(paths(&value.query("//a/*//b").unwrap()), ["/a/x/a/b"]);
+    }
+
+    #[test]
+    fn select_session_entries() {
+        let session =
+            session_decode(b"cart|a:1:{s:5:\"items\";a:1:{i:0;a:1:{s:3:\"sku\";i:42;}}}!gone|")
+                .unwrap();
+        let found = query_session(&session, "/cart/items/*/sku").unwrap();
+        assert_eq!(paths(&found), ["/cart/items/0/sku"]);
+        assert_eq!(found[0].value, &Value::Integer(42));
+
+        assert_eq!(query_session(&session, "/gone").unwrap(), []);
+        assert_eq!(query_session(&session, "").unwrap(), []);
+        assert_eq!(paths(&query_session(&session, "/*").unwrap()), ["/cart"]);
+        assert_eq!(query_session(&session, "//sku").unwrap().len(), 1);
+    }
+}
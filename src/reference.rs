@@ -0,0 +1,581 @@
+use std::{borrow::Cow, num::NonZeroUsize, ops::Index};
+
+use crate::{
+    error::Error,
+    path::{Path, PathSegment},
+    value::{ArrayKey, ObjectPropertyVisibility, SessionEntry, Value},
+};
+
+fn is_object(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Object { .. }
+            | Value::CustomObject { .. }
+            | Value::Enum { .. }
+            | Value::ObjectReference(_)
+    )
+}
+
+fn count_slots(value: &Value) -> usize {
+    match value {
+        Value::ValueReference(_) => 0,
+        Value::Array(items) => {
+            1 + items
+                .iter()
+                .map(|(_, value)| count_slots(value))
+                .sum::<usize>()
+        }
+        Value::Object { properties, .. } => {
+            1 + properties
+                .iter()
+                .map(|property| count_slots(&property.value))
+                .sum::<usize>()
+        }
+        _ => 1,
+    }
+}
+
+/// Value numbered by PHP's `unserialize`, that `R:` and `r:` may point to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot<'v> {
+    pub path: Path<'v>,
+    pub value: &'v Value<'v>,
+}
+
+/// Slots of a value or session, numbered like PHP does.
+///
+/// Every value gets a slot in the order it appears in the serialized data, starting from 1,
+/// except `R:` references. Array keys and property names don't get any. Session entries share a
+/// single numbering.
+///
+/// ```
+/// use muesli::{unserialize, SlotTable};
+///
+/// let value = unserialize(b"a:2:{s:1:\"a\";a:1:{i:0;O:8:\"stdClass\":0:{}}s:1:\"b\";r:3;}").unwrap();
+/// let slots = SlotTable::new(&value).unwrap();
+///
+/// assert_eq!(slots.len(), 4);
+/// assert_eq!(slots.get(3).unwrap().path.to_string(), "/a/0");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlotTable<'v> {
+    slots: Vec<Slot<'v>>,
+}
+
+impl<'v> SlotTable<'v> {
+    /// Number the slots of `value`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a reference points to a missing slot, to a slot that comes after
+    /// it, or if an object reference doesn't point to an object.
+    pub fn new(value: &'v Value<'v>) -> Result<Self, Error> {
+        let mut table = Self::default();
+        table.visit(value, &mut Path::new(), count_slots(value))?;
+        Ok(table)
+    }
+
+    /// Number the slots of a session's values.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a reference points to a missing slot, to a slot that comes after
+    /// it, or if an object reference doesn't point to an object.
+    pub fn from_session(entries: &'v [SessionEntry<'v>]) -> Result<Self, Error> {
+        let total = entries
+            .iter()
+            .filter_map(|entry| entry.value.as_ref())
+            .map(count_slots)
+            .sum();
+        let mut table = Self::default();
+        let mut path = Path::new();
+        for entry in entries {
+            if let Some(value) = &entry.value {
+                path.push(PathSegment::Entry(Cow::Borrowed(&entry.key)));
+                table.visit(value, &mut path, total)?;
+                path.pop();
+            }
+        }
+        Ok(table)
+    }
+
+    /// Slot numbered `index`, as written in `R:index;` or `r:index;`.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&Slot<'v>> {
+        self.slots.get(index.checked_sub(1)?)
+    }
+
+    /// Slot pointed to by a `R:` or `r:` reference.
+    #[must_use]
+    pub fn target(&self, reference: &Value) -> Option<&Slot<'v>> {
+        match reference {
+            Value::ValueReference(index) | Value::ObjectReference(index) => self.get(index.get()),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    fn check(&self, index: NonZeroUsize, path: &Path, total: usize) -> Result<&Slot<'v>, Error> {
+        let index = index.get();
+        match self.get(index) {
+            Some(slot) => Ok(slot),
+            None if index <= total => Err(Error::ForwardReference {
+                path: path.to_string(),
+                index,
+            }),
+            None => Err(Error::DanglingReference {
+                path: path.to_string(),
+                index,
+            }),
+        }
+    }
+
+    fn visit(
+        &mut self,
+        value: &'v Value<'v>,
+        path: &mut Path<'v>,
+        total: usize,
+    ) -> Result<(), Error> {
+        match value {
+            Value::ValueReference(index) => {
+                self.check(*index, path, total)?;
+                return Ok(());
+            }
+            Value::ObjectReference(index) => {
+                let target = self.check(*index, path, total)?;
+                if !is_object(target.value) {
+                    return Err(Error::NotAnObject {
+                        path: path.to_string(),
+                        index: index.get(),
+                    });
+                }
+            }
+            _ => (),
+        }
+        self.slots.push(Slot {
+            path: path.clone(),
+            value,
+        });
+        match value {
+            Value::Array(items) => {
+                for (key, value) in items {
+                    path.push(PathSegment::Key(key.as_borrowed()));
+                    self.visit(value, path, total)?;
+                    path.pop();
+                }
+            }
+            Value::Object { properties, .. } => {
+                for property in properties {
+                    path.push(PathSegment::Property(Cow::Borrowed(&property.name)));
+                    self.visit(&property.value, path, total)?;
+                    path.pop();
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Index of a [`Node`] within a [`Graph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeProperty<'a> {
+    pub visibility: ObjectPropertyVisibility<'a>,
+    pub name: Cow<'a, [u8]>,
+    pub value: NodeId,
+}
+
+/// Value of a [`Graph`], pointing to its children by id.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node<'a> {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Decimal(f64),
+    String(Cow<'a, [u8]>),
+    Array(Vec<(ArrayKey<'a>, NodeId)>),
+    Object {
+        class_name: Cow<'a, [u8]>,
+        properties: Vec<NodeProperty<'a>>,
+    },
+    CustomObject {
+        class_name: Cow<'a, [u8]>,
+        data: Cow<'a, [u8]>,
+    },
+    Enum {
+        class_name: Cow<'a, [u8]>,
+        case: Cow<'a, [u8]>,
+    },
+    /// PHP reference (`&`), shared by every place holding the referenced value.
+    Reference(NodeId),
+}
+
+/// Session entry of a [`Graph`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphEntry<'a> {
+    pub key: Cow<'a, [u8]>,
+    /// `None` for variables that were undefined when the session was written.
+    pub value: Option<NodeId>,
+}
+
+/// Arena of values where references are resolved.
+///
+/// Places holding the same object point to the same node, as `r:` does. Places bound together by
+/// a PHP reference point to the same [`Node::Reference`], as `R:` does. Graphs may be cyclic.
+///
+/// ```
+/// use muesli::{unserialize, Graph, Node};
+///
+/// let value = unserialize(b"a:2:{i:0;O:8:\"stdClass\":0:{}i:1;r:2;}").unwrap();
+/// let (graph, root) = Graph::from_value(value).unwrap();
+///
+/// let Node::Array(items) = &graph[root] else { panic!() };
+/// assert_eq!(items[0].1, items[1].1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Graph<'a> {
+    nodes: Vec<Node<'a>>,
+}
+
+/// Where a slot's value is held, so that `R:` can turn it into a reference.
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Root(usize),
+    Child { parent: NodeId, index: usize },
+}
+
+struct Builder<'a> {
+    graph: Graph<'a>,
+    roots: Vec<NodeId>,
+    slots: Vec<Location>,
+}
+
+impl<'a> Builder<'a> {
+    fn new() -> Self {
+        Self {
+            graph: Graph::new(),
+            roots: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    fn edge(&self, location: Location) -> NodeId {
+        match location {
+            Location::Root(index) => self.roots[index],
+            Location::Child { parent, index } => match &self.graph[parent] {
+                Node::Array(items) => items[index].1,
+                Node::Object { properties, .. } => properties[index].value,
+                _ => unreachable!("only arrays and objects have children"),
+            },
+        }
+    }
+
+    fn set_edge(&mut self, location: Location, id: NodeId) {
+        match location {
+            Location::Root(index) => self.roots[index] = id,
+            Location::Child { parent, index } => match &mut self.graph.nodes[parent.0] {
+                Node::Array(items) => items[index].1 = id,
+                Node::Object { properties, .. } => properties[index].value = id,
+                _ => unreachable!("only arrays and objects have children"),
+            },
+        }
+    }
+
+    /// Add a root value, returning its index among roots. Roots may later be turned into
+    /// references, so their node is only known once the whole graph is built.
+    fn add_root(&mut self, value: Value<'a>) -> usize {
+        let index = self.roots.len();
+        self.roots.push(NodeId(0));
+        self.build(value, Location::Root(index));
+        index
+    }
+
+    /// Add `value` to the graph, and point `location` to it. References were checked by
+    /// [`SlotTable`] beforehand.
+    fn build(&mut self, value: Value<'a>, location: Location) {
+        let node = match value {
+            Value::ValueReference(index) => {
+                let target = self.slots[index.get() - 1];
+                let id = self.edge(target);
+                let reference = if let Node::Reference(_) = self.graph[id] {
+                    id
+                } else {
+                    let reference = self.graph.insert(Node::Reference(id));
+                    self.set_edge(target, reference);
+                    reference
+                };
+                self.set_edge(location, reference);
+                return;
+            }
+            Value::ObjectReference(index) => {
+                self.slots.push(location);
+                let mut id = self.edge(self.slots[index.get() - 1]);
+                if let Node::Reference(target) = self.graph[id] {
+                    id = target;
+                }
+                self.set_edge(location, id);
+                return;
+            }
+            Value::Null => Node::Null,
+            Value::Boolean(b) => Node::Boolean(b),
+            Value::Integer(n) => Node::Integer(n),
+            Value::Decimal(d) => Node::Decimal(d),
+            Value::String(s) => Node::String(s),
+            Value::Array(items) => {
+                self.slots.push(location);
+                let parent = self
+                    .graph
+                    .insert(Node::Array(Vec::with_capacity(items.len())));
+                self.set_edge(location, parent);
+                for (index, (key, value)) in items.into_iter().enumerate() {
+                    if let Node::Array(items) = &mut self.graph.nodes[parent.0] {
+                        items.push((key, parent));
+                    }
+                    self.build(value, Location::Child { parent, index });
+                }
+                return;
+            }
+            Value::Object {
+                class_name,
+                properties,
+            } => {
+                self.slots.push(location);
+                let parent = self.graph.insert(Node::Object {
+                    class_name,
+                    properties: Vec::with_capacity(properties.len()),
+                });
+                self.set_edge(location, parent);
+                for (index, property) in properties.into_iter().enumerate() {
+                    if let Node::Object { properties, .. } = &mut self.graph.nodes[parent.0] {
+                        properties.push(NodeProperty {
+                            visibility: property.visibility,
+                            name: property.name,
+                            value: parent,
+                        });
+                    }
+                    self.build(property.value, Location::Child { parent, index });
+                }
+                return;
+            }
+            Value::CustomObject { class_name, data } => Node::CustomObject { class_name, data },
+            Value::Enum { class_name, case } => Node::Enum { class_name, case },
+        };
+        self.slots.push(location);
+        let id = self.graph.insert(node);
+        self.set_edge(location, id);
+    }
+}
+
+impl<'a> Graph<'a> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the graph of `value`, returning it along with the id of its root node.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `value` holds invalid references, see [`SlotTable::new`].
+    pub fn from_value(value: Value<'a>) -> Result<(Self, NodeId), Error> {
+        SlotTable::new(&value)?;
+        let mut builder = Builder::new();
+        let root = builder.add_root(value);
+        Ok((builder.graph, builder.roots[root]))
+    }
+
+    /// Build the graph of a session, returning it along with its entries.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `entries` hold invalid references, see [`SlotTable::from_session`].
+    pub fn from_session(
+        entries: Vec<SessionEntry<'a>>,
+    ) -> Result<(Self, Vec<GraphEntry<'a>>), Error> {
+        SlotTable::from_session(&entries)?;
+        let mut builder = Builder::new();
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.value.map(|value| builder.add_root(value))))
+            .collect();
+        let entries = entries
+            .into_iter()
+            .map(|(key, root)| GraphEntry {
+                key,
+                value: root.map(|root| builder.roots[root]),
+            })
+            .collect();
+        Ok((builder.graph, entries))
+    }
+
+    /// Add a node, returning its id.
+    pub fn insert(&mut self, node: Node<'a>) -> NodeId {
+        self.nodes.push(node);
+        NodeId(self.nodes.len() - 1)
+    }
+
+    #[must_use]
+    pub fn get(&self, id: NodeId) -> Option<&Node<'a>> {
+        self.nodes.get(id.0)
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node<'a>> {
+        self.nodes.get_mut(id.0)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl<'a> Index<NodeId> for Graph<'a> {
+    type Output = Node<'a>;
+
+    fn index(&self, id: NodeId) -> &Self::Output {
+        &self.nodes[id.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session_decode, unserialize};
+
+    use super::*;
+
+    #[test]
+    fn number_slots() {
+        let value = unserialize(
+            b"a:3:{i:0;O:3:\"Foo\":2:{s:1:\"a\";i:1;s:1:\"b\";R:3;}s:1:\"c\";r:2;i:1;R:2;}",
+        )
+        .unwrap();
+        let slots = SlotTable::new(&value).unwrap();
+        let paths: Vec<_> = slots
+            .slots
+            .iter()
+            .map(|slot| slot.path.to_string())
+            .collect();
+        assert_eq!(paths, ["", "/0", "/0/a", "/c"]);
+        assert_eq!(slots.get(0), None);
+        assert_eq!(slots.get(3).unwrap().value, &Value::Integer(1));
+        assert_eq!(
+            slots.target(&Value::ObjectReference(NonZeroUsize::MIN.saturating_add(1))),
+            slots.get(2)
+        );
+
+        let session = session_decode(b"a|i:1;!b|c|a:1:{i:0;R:1;}d|R:2;").unwrap();
+        let slots = SlotTable::from_session(&session).unwrap();
+        let paths: Vec<_> = slots
+            .slots
+            .iter()
+            .map(|slot| slot.path.to_string())
+            .collect();
+        assert_eq!(paths, ["/a", "/c"]);
+    }
+
+    #[test]
+    fn reject_invalid_references() {
+        let value = unserialize(b"a:3:{i:0;R:3;i:1;i:1;i:2;i:2;}").unwrap();
+        assert!(matches!(
+            SlotTable::new(&value),
+            Err(Error::ForwardReference { path, index: 3 }) if path == "/0"
+        ));
+
+        let value = unserialize(b"a:1:{i:0;R:3;}").unwrap();
+        assert!(matches!(
+            SlotTable::new(&value),
+            Err(Error::DanglingReference { path, index: 3 }) if path == "/0"
+        ));
+
+        let value = unserialize(b"a:2:{i:0;i:1;s:1:\"x\";r:2;}").unwrap();
+        assert!(matches!(
+            Graph::from_value(value),
+            Err(Error::NotAnObject { path, index: 2 }) if path == "/x"
+        ));
+
+        let session = session_decode(b"a|R:1;").unwrap();
+        assert!(matches!(
+            SlotTable::from_session(&session),
+            Err(Error::DanglingReference { path, index: 1 }) if path == "/a"
+        ));
+    }
+
+    #[test]
+    fn build_graph() {
+        let value =
+            unserialize(b"a:3:{i:0;O:3:\"Foo\":1:{s:1:\"a\";i:1;}i:1;r:2;i:2;R:3;}").unwrap();
+        let (graph, root) = Graph::from_value(value).unwrap();
+
+        let Node::Array(items) = &graph[root] else {
+            panic!("root is not an array");
+        };
+        let object = items[0].1;
+        assert_eq!(items[1].1, object);
+        let Node::Object { properties, .. } = &graph[object] else {
+            panic!("first item is not an object");
+        };
+        let reference = properties[0].value;
+        assert_eq!(items[2].1, reference);
+        let Node::Reference(integer) = graph[reference] else {
+            panic!("property is not a reference");
+        };
+        assert_eq!(graph[integer], Node::Integer(1));
+    }
+
+    #[test]
+    fn build_cyclic_graph() {
+        let value = unserialize(b"a:1:{i:0;a:1:{i:0;R:2;}}").unwrap();
+        let (graph, root) = Graph::from_value(value).unwrap();
+
+        let Node::Array(items) = &graph[root] else {
+            panic!("root is not an array");
+        };
+        let Node::Reference(inner) = graph[items[0].1] else {
+            panic!("item is not a reference");
+        };
+        let Node::Array(inner_items) = &graph[inner] else {
+            panic!("reference doesn't point to an array");
+        };
+        assert_eq!(inner_items[0].1, items[0].1);
+    }
+
+    #[test]
+    fn build_session_graph() {
+        let session = session_decode(b"a|O:8:\"stdClass\":0:{}!b|c|r:1;d|R:1;").unwrap();
+        let (graph, entries) = Graph::from_session(session).unwrap();
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[1].value, None);
+        let reference = entries[0].value.unwrap();
+        assert_eq!(entries[3].value, Some(reference));
+        let Node::Reference(object) = graph[reference] else {
+            panic!("entry is not a reference");
+        };
+        assert_eq!(entries[2].value, Some(object));
+        assert!(matches!(graph[object], Node::Object { .. }));
+    }
+}
@@ -1,123 +1,389 @@
-use std::io::Write;
+mod graph;
+#[cfg(feature = "serde")]
+pub(crate) mod serde;
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    iter,
+};
+
+pub use graph::{serialize_graph, session_encode_graph};
 
 use crate::{
-    value::{ArrayKey, SessionEntry, Value},
+    error::Error,
+    value::{canonical_integer, ArrayKey, SessionEntry, SessionFormat, Value},
     ObjectPropertyVisibility,
 };
 
+/// Options tuning how values are encoded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodeOptions {
+    escape_strings: bool,
+    session_format: SessionFormat,
+    serialize_precision: Option<u32>,
+    canonical_keys: bool,
+}
+
+impl EncodeOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write strings and array keys with the legacy `S:` token, where backslashes and bytes
+    /// outside of printable ASCII are written as `\xx` hex escapes.
+    #[must_use]
+    pub fn with_escaped_strings(mut self, escape_strings: bool) -> Self {
+        self.escape_strings = escape_strings;
+        self
+    }
+
+    /// Significant digits of floats, like PHP's `serialize_precision` setting.
+    ///
+    /// `None`, the default, writes the shortest digits reading back as the same float, as
+    /// `serialize_precision = -1` does since PHP 7.1. `Some(17)` emulates the former default.
+    #[must_use]
+    pub fn with_serialize_precision(mut self, serialize_precision: Option<u32>) -> Self {
+        self.serialize_precision = serialize_precision;
+        self
+    }
+
+    /// Write string array keys holding an integer, like `"42"`, as integers, as PHP stores them.
+    ///
+    /// See [`NumericKeys`](crate::NumericKeys) for the keys this applies to.
+    #[must_use]
+    pub fn with_canonical_keys(mut self, canonical_keys: bool) -> Self {
+        self.canonical_keys = canonical_keys;
+        self
+    }
+
+    /// Format written by `session_encode_with`.
+    #[must_use]
+    pub fn with_session_format(mut self, session_format: SessionFormat) -> Self {
+        self.session_format = session_format;
+        self
+    }
+}
+
+fn needs_escape(byte: u8) -> bool {
+    !(b' '..=b'~').contains(&byte) || byte == b'\\'
+}
+
+/// Format a finite float as PHP's `zend_gcvt` does, e.g. `0.1`, `100`, or `1.0E+25`.
+pub(crate) fn format_decimal(d: f64, precision: Option<u32>) -> String {
+    // Significant digits and exponent of the first one, in scientific notation.
+    let scientific = match precision {
+        None => format!("{:e}", d.abs()),
+        Some(precision) => format!("{:.*e}", precision.max(1) as usize - 1, d.abs()),
+    };
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i64 = exponent.parse().unwrap_or_default();
+    let mut digits = mantissa.replace('.', "");
+    while digits.len() > 1 && digits.ends_with('0') {
+        digits.pop();
+    }
+
+    let mut formatted = String::new();
+    if d.is_sign_negative() {
+        formatted.push('-');
+    }
+    // Shortest digits switch to exponential notation as if precision were 17.
+    let threshold = precision.map_or(17, |precision| precision.max(1));
+    if exponent < -4 || exponent >= i64::from(threshold) {
+        let (first, rest) = digits.split_at(1);
+        let rest = if rest.is_empty() { "0" } else { rest };
+        let sign = if exponent < 0 { '-' } else { '+' };
+        let _ = write!(
+            formatted,
+            "{first}.{rest}E{sign}{}",
+            exponent.unsigned_abs()
+        );
+    } else if exponent < 0 {
+        formatted.push_str("0.");
+        let zeros = usize::try_from(-exponent - 1).unwrap_or_default();
+        formatted.extend(iter::repeat('0').take(zeros));
+        formatted.push_str(&digits);
+    } else {
+        let integer_len = usize::try_from(exponent).unwrap_or_default() + 1;
+        if digits.len() > integer_len {
+            digits.insert(integer_len, '.');
+        } else {
+            digits.extend(iter::repeat('0').take(integer_len - digits.len()));
+        }
+        formatted.push_str(&digits);
+    }
+    formatted
+}
+
+/// Write all of `bytes`, returning their length.
+fn put<W: Write>(w: &mut W, bytes: &[u8]) -> Result<usize, Error> {
+    w.write_all(bytes)?;
+    Ok(bytes.len())
+}
+
+fn write_string<W: Write>(
+    w: &mut W,
+    string: &[u8],
+    options: &EncodeOptions,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    if options.escape_strings {
+        count += put(w, format!("S:{}:\"", string.len()).as_bytes())?;
+        for chunk in string.split_inclusive(|byte| needs_escape(*byte)) {
+            match chunk.split_last() {
+                Some((last, plain)) if needs_escape(*last) => {
+                    count += put(w, plain)?;
+                    count += put(w, format!("\\{last:02x}").as_bytes())?;
+                }
+                _ => count += put(w, chunk)?,
+            }
+        }
+    } else {
+        count += put(w, format!("s:{}:\"", string.len()).as_bytes())?;
+        count += put(w, string)?;
+    }
+    count += put(w, b"\";")?;
+    Ok(count)
+}
+
+/// Write an array key, see [`write_string_key`].
+fn write_key<W: Write>(w: &mut W, key: &ArrayKey, options: &EncodeOptions) -> Result<usize, Error> {
+    match key {
+        ArrayKey::Integer(n) => put(w, format!("i:{n};").as_bytes()),
+        ArrayKey::String(key) => write_string_key(w, key, options),
+    }
+}
+
+/// Write a string array key, as an integer when [`EncodeOptions::with_canonical_keys`] is set
+/// and PHP would store it as one.
+fn write_string_key<W: Write>(
+    w: &mut W,
+    key: &[u8],
+    options: &EncodeOptions,
+) -> Result<usize, Error> {
+    match canonical_integer(key).filter(|_| options.canonical_keys) {
+        Some(n) => put(w, format!("i:{n};").as_bytes()),
+        None => write_string(w, key, options),
+    }
+}
+
+/// Write the name of an object property, mangled according to its visibility.
+fn write_property_name<W: Write>(
+    w: &mut W,
+    visibility: &ObjectPropertyVisibility,
+    name: &[u8],
+) -> Result<usize, Error> {
+    let mut count = 0;
+    match visibility {
+        ObjectPropertyVisibility::Public => {
+            count += put(w, format!("s:{}:\"", name.len()).as_bytes())?;
+        }
+        ObjectPropertyVisibility::Protected => {
+            count += put(w, format!("s:{}:\"\0*\0", name.len() + 3).as_bytes())?;
+        }
+        ObjectPropertyVisibility::Private(class_name) => {
+            count += put(
+                w,
+                format!("s:{}:\"\0", name.len() + 2 + class_name.len()).as_bytes(),
+            )?;
+            count += put(w, class_name)?;
+            count += put(w, b"\0")?;
+        }
+    }
+    count += put(w, name)?;
+    count += put(w, b"\";")?;
+    Ok(count)
+}
+
 /// Encode data to PHP's `serialize` format
 ///
 /// # Errors
 ///
 /// Will return `Err` if write fail
-pub fn serialize<W: Write>(w: &mut W, value: &Value) -> std::result::Result<usize, std::io::Error> {
+pub fn serialize<W: Write>(w: &mut W, value: &Value) -> Result<usize, Error> {
+    serialize_with(w, value, &EncodeOptions::default())
+}
+
+/// Encode data to PHP's `serialize` format, using the given options.
+///
+/// # Errors
+///
+/// Will return `Err` if write fail
+pub fn serialize_with<W: Write>(
+    w: &mut W,
+    value: &Value,
+    options: &EncodeOptions,
+) -> Result<usize, Error> {
     match value {
-        Value::Null => w.write(b"N;"),
-        Value::Boolean(false) => w.write(b"b:0;"),
-        Value::Boolean(true) => w.write(b"b:1;"),
-        Value::Integer(n) => w.write(format!("i:{n};").as_bytes()),
+        Value::Null => put(w, b"N;"),
+        Value::Boolean(false) => put(w, b"b:0;"),
+        Value::Boolean(true) => put(w, b"b:1;"),
+        Value::Integer(n) => put(w, format!("i:{n};").as_bytes()),
         Value::Decimal(d) => {
             if d.is_nan() {
-                w.write(b"d:NAN;")
+                put(w, b"d:NAN;")
             } else if d.is_infinite() {
                 if d.is_sign_positive() {
-                    w.write(b"d:INF;")
+                    put(w, b"d:INF;")
                 } else {
-                    w.write(b"d:-INF;")
+                    put(w, b"d:-INF;")
                 }
             } else {
-                w.write(format!("d:{d};").as_bytes())
+                put(
+                    w,
+                    format!("d:{};", format_decimal(*d, options.serialize_precision)).as_bytes(),
+                )
             }
         }
-        Value::String(string) => {
-            let mut count = 0;
-            count += w.write(format!("s:{}:\"", string.len()).as_bytes())?;
-            count += w.write(string)?;
-            count += w.write(b"\";")?;
-            Ok(count)
-        }
+        Value::String(string) => write_string(w, string, options),
         Value::Array(items) => {
             let mut count = 0;
-            count += w.write(format!("a:{}:{{", items.len()).as_bytes())?;
+            count += put(w, format!("a:{}:{{", items.len()).as_bytes())?;
             for (key, value) in items {
-                match key {
-                    ArrayKey::Integer(key) => {
-                        count += w.write(format!("i:{key};").as_bytes())?;
-                    }
-                    ArrayKey::String(key) => {
-                        count += w.write(format!("s:{}:\"", key.len()).as_bytes())?;
-                        count += w.write(key)?;
-                        count += w.write(b"\";")?;
-                    }
-                }
-                count += serialize(w, value)?;
+                count += write_key(w, key, options)?;
+                count += serialize_with(w, value, options)?;
             }
-            count += w.write(b"}")?;
+            count += put(w, b"}")?;
             Ok(count)
         }
-        Value::ValueReference(idx) => w.write(format!("R:{idx};").as_bytes()),
-        Value::ObjectReference(idx) => w.write(format!("r:{idx};").as_bytes()),
+        Value::ValueReference(idx) => put(w, format!("R:{idx};").as_bytes()),
+        Value::ObjectReference(idx) => put(w, format!("r:{idx};").as_bytes()),
         Value::Object {
             class_name,
             properties,
         } => {
             let mut count = 0;
-            count += w.write(format!("O:{}:\"", class_name.len()).as_bytes())?;
-            count += w.write(class_name)?;
-            count += w.write(format!("\":{}:{{", properties.len()).as_bytes())?;
+            count += put(w, format!("O:{}:\"", class_name.len()).as_bytes())?;
+            count += put(w, class_name)?;
+            count += put(w, format!("\":{}:{{", properties.len()).as_bytes())?;
             for property in properties {
-                use ObjectPropertyVisibility::{Private, Protected, Public};
-
-                match property.visibility {
-                    Public => {
-                        count += w.write(format!("s:{}:\"", property.name.len()).as_bytes())?;
-                    }
-                    Protected => {
-                        count +=
-                            w.write(format!("s:{}:\"\0*\0", property.name.len() + 3).as_bytes())?;
-                    }
-                    Private => {
-                        count += w.write(
-                            format!("s:{}:\"\0", property.name.len() + 2 + class_name.len())
-                                .as_bytes(),
-                        )?;
-                        count += w.write(class_name)?;
-                        count += w.write(b"\0")?;
-                    }
-                }
-                count += w.write(property.name)?;
-                count += w.write(b"\";")?;
-                count += serialize(w, &property.value)?;
+                count += write_property_name(w, &property.visibility, &property.name)?;
+                count += serialize_with(w, &property.value, options)?;
             }
-            count += w.write(b"}")?;
+            count += put(w, b"}")?;
             Ok(count)
         }
         Value::CustomObject { class_name, data } => {
             let mut count = 0;
-            count += w.write(format!("C:{}:\"", class_name.len()).as_bytes())?;
-            count += w.write(class_name)?;
-            count += w.write(format!("\":{}:{{", data.len()).as_bytes())?;
-            count += w.write(data)?;
-            count += w.write(b"}")?;
+            count += put(w, format!("C:{}:\"", class_name.len()).as_bytes())?;
+            count += put(w, class_name)?;
+            count += put(w, format!("\":{}:{{", data.len()).as_bytes())?;
+            count += put(w, data)?;
+            count += put(w, b"}")?;
+            Ok(count)
+        }
+        Value::Enum { class_name, case } => {
+            let mut count = 0;
+            count += put(
+                w,
+                format!("E:{}:\"", class_name.len() + 1 + case.len()).as_bytes(),
+            )?;
+            count += put(w, class_name)?;
+            count += put(w, b":")?;
+            count += put(w, case)?;
+            count += put(w, b"\";")?;
             Ok(count)
         }
     }
 }
 
+/// Length of the output of [`serialize`] for a value, e.g. to size a buffer or to send a
+/// `Content-Length` before the data.
+#[must_use]
+pub fn serialized_len(value: &Value) -> usize {
+    serialized_len_with(value, &EncodeOptions::default())
+}
+
+/// Length of the output of [`serialize_with`] for a value and the given options.
+#[must_use]
+pub fn serialized_len_with(value: &Value, options: &EncodeOptions) -> usize {
+    // Writing to a sink never fails.
+    serialize_with(&mut io::sink(), value, options).unwrap_or_default()
+}
+
+const BINARY_MAX_KEY_LENGTH: u8 = 127;
+/// Flag set on the key length of `php_binary` entries holding an undefined variable.
+const BINARY_UNDEFINED: u8 = 0x80;
+
 /// Encode data to PHP's session format, compatible with `session_decode()`.
 ///
 /// # Errors
 ///
 /// Will return `Err` if write fail
-pub fn session_encode<W: Write>(
+pub fn session_encode<W: Write>(w: &mut W, session: &[SessionEntry]) -> Result<usize, Error> {
+    session_encode_with(w, session, &EncodeOptions::default())
+}
+
+/// Encode data to PHP's session format, using the given options.
+///
+/// # Errors
+///
+/// Will return `Err` if write fail, or if a key can't be written in the selected format.
+pub fn session_encode_with<W: Write>(
     w: &mut W,
     session: &[SessionEntry],
-) -> std::result::Result<usize, std::io::Error> {
+    options: &EncodeOptions,
+) -> Result<usize, Error> {
+    write_session(
+        w,
+        session
+            .iter()
+            .map(|entry| (entry.key.as_ref(), entry.value.as_ref())),
+        options,
+        |w, value| serialize_with(w, value, options),
+    )
+}
+
+/// Write session entries in the selected format, values being written by `write_value`.
+fn write_session<'k, W: Write, V>(
+    w: &mut W,
+    entries: impl Iterator<Item = (&'k [u8], Option<V>)> + Clone,
+    options: &EncodeOptions,
+    mut write_value: impl FnMut(&mut W, V) -> Result<usize, Error>,
+) -> Result<usize, Error> {
     let mut count = 0;
-    for entry in session {
-        count += w.write(entry.key)?;
-        count += w.write(b"|")?;
-        count += serialize(w, &entry.value)?;
+    match options.session_format {
+        SessionFormat::Php => {
+            for (key, value) in entries {
+                // Such keys would be read back as another key, or as an undefined variable.
+                if key.contains(&b'|') || key.first() == Some(&b'!') {
+                    return Err(Error::InvalidSessionKey { key: key.to_vec() });
+                }
+                if value.is_none() {
+                    count += put(w, b"!")?;
+                }
+                count += put(w, key)?;
+                count += put(w, b"|")?;
+                if let Some(value) = value {
+                    count += write_value(w, value)?;
+                }
+            }
+        }
+        SessionFormat::PhpSerialize => {
+            // Undefined variables are left out, as an array has no way to hold them.
+            let defined = entries.filter_map(|(key, value)| Some((key, value?)));
+            count += put(w, format!("a:{}:{{", defined.clone().count()).as_bytes())?;
+            for (key, value) in defined {
+                count += write_string_key(w, key, options)?;
+                count += write_value(w, value)?;
+            }
+            count += put(w, b"}")?;
+        }
+        SessionFormat::PhpBinary => {
+            for (key, value) in entries {
+                let length = match u8::try_from(key.len()) {
+                    Ok(length) if length <= BINARY_MAX_KEY_LENGTH => length,
+                    _ => return Err(Error::InvalidSessionKey { key: key.to_vec() }),
+                };
+                let flag = if value.is_some() { 0 } else { BINARY_UNDEFINED };
+                count += put(w, &[length | flag])?;
+                count += put(w, key)?;
+                if let Some(value) = value {
+                    count += write_value(w, value)?;
+                }
+            }
+        }
     }
     Ok(count)
 }
@@ -137,6 +403,7 @@ mod tests {
             let count = serialize(&mut buffer, input).unwrap();
             assert_eq!(&buffer.as_slice(), expected);
             assert_eq!(count, expected.len());
+            assert_eq!(serialized_len(input), expected.len());
         }
     }
 
@@ -183,6 +450,8 @@ mod tests {
             (Value::Decimal(0.0), b"d:0;".as_slice()),
             (Value::Decimal(0.2), b"d:0.2;".as_slice()),
             (Value::Decimal(-0.2), b"d:-0.2;".as_slice()),
+            (Value::Decimal(1e25), b"d:1.0E+25;".as_slice()),
+            (Value::Decimal(1e-7), b"d:1.0E-7;".as_slice()),
             (Value::Decimal(f64::NAN), b"d:NAN;".as_slice()),
             (Value::Decimal(f64::INFINITY), b"d:INF;".as_slice()),
             (Value::Decimal(f64::NEG_INFINITY), b"d:-INF;".as_slice()),
@@ -190,15 +459,82 @@ mod tests {
         run_encode_cases(&cases);
     }
 
+    #[test]
+    fn format_decimal_like_php() {
+        let cases = [
+            (-0.0, "-0"),
+            (1.0, "1"),
+            (100.0, "100"),
+            (1.5, "1.5"),
+            (-123_456.789, "-123456.789"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (0.0001, "0.0001"),
+            (0.000_012_3, "1.23E-5"),
+            (1e-7, "1.0E-7"),
+            (1e16, "10000000000000000"),
+            (1e17, "1.0E+17"),
+            (1e25, "1.0E+25"),
+            (-1.5e300, "-1.5E+300"),
+            (f64::MAX, "1.7976931348623157E+308"),
+        ];
+        for (d, expected) in cases {
+            assert_eq!(format_decimal(d, None), expected, "{d:e}");
+        }
+
+        let cases = [
+            (0.1, "0.10000000000000001"),
+            (0.5, "0.5"),
+            (100.0, "100"),
+            (1e25, "1.0000000000000001E+25"),
+            (1e-7, "9.9999999999999995E-8"),
+            (123_456_789_012_345_680_000.0, "1.2345678901234568E+20"),
+        ];
+        for (d, expected) in cases {
+            assert_eq!(format_decimal(d, Some(17)), expected, "{d:e}");
+        }
+        assert_eq!(format_decimal(1234.5, Some(3)), "1.23E+3");
+        assert_eq!(format_decimal(0.000_123_45, Some(3)), "0.000123");
+
+        let mut buffer = Vec::new();
+        let options = EncodeOptions::new().with_serialize_precision(Some(17));
+        serialize_with(&mut buffer, &Value::Decimal(0.1), &options).unwrap();
+        assert_eq!(buffer, b"d:0.10000000000000001;");
+    }
+
     #[test]
     fn encode_value_string() {
         let cases = [
-            (Value::String(b"".as_slice()), b"s:0:\"\";".as_slice()),
-            (Value::String(b"foo".as_slice()), b"s:3:\"foo\";".as_slice()),
+            (Value::String(b"".into()), b"s:0:\"\";".as_slice()),
+            (Value::String(b"foo".into()), b"s:3:\"foo\";".as_slice()),
         ];
         run_encode_cases(&cases);
     }
 
+    #[test]
+    fn encode_escaped_string() {
+        let options = EncodeOptions::new().with_escaped_strings(true);
+        let cases = [
+            (Value::String(b"".into()), b"S:0:\"\";".as_slice()),
+            (Value::String(b"foo".into()), b"S:3:\"foo\";".as_slice()),
+            (
+                Value::String(b"a\0b\\c\xffd\n".into()),
+                b"S:8:\"a\\00b\\5cc\\ffd\\0a\";".as_slice(),
+            ),
+            (
+                Value::Array(vec![(ArrayKey::String(b"\t".into()), Value::Integer(1))]),
+                b"a:1:{S:1:\"\\09\";i:1;}".as_slice(),
+            ),
+        ];
+
+        let mut buffer = Vec::<u8>::new();
+        for (input, expected) in cases {
+            buffer.clear();
+            let count = serialize_with(&mut buffer, &input, &options).unwrap();
+            assert_eq!(buffer.as_slice(), expected);
+            assert_eq!(count, expected.len());
+        }
+    }
+
     #[test]
     fn encode_value_references() {
         let cases = [
@@ -219,26 +555,31 @@ mod tests {
         let cases = [
             (
                 Value::Object {
-                    class_name: b"Test".as_slice(),
+                    class_name: b"Test".into(),
                     properties: vec![
                         ObjectProperty {
-                            name: b"public".as_slice(),
+                            name: b"public".into(),
                             visibility: ObjectPropertyVisibility::Public,
                             value: Value::Integer(1),
                         },
                         ObjectProperty {
-                            name: b"protected".as_slice(),
+                            name: b"protected".into(),
                             visibility: ObjectPropertyVisibility::Protected,
                             value: Value::Integer(2),
                         },
                         ObjectProperty {
-                            name: b"private".as_slice(),
-                            visibility: ObjectPropertyVisibility::Private,
+                            name: b"private".into(),
+                            visibility: ObjectPropertyVisibility::Private(b"Test".into()),
                             value: Value::Integer(3),
                         },
+                        ObjectProperty {
+                            name: b"inherited".into(),
+                            visibility: ObjectPropertyVisibility::Private(b"Parent".into()),
+                            value: Value::Integer(4),
+                        },
                     ],
                 },
-                b"O:4:\"Test\":3:{s:6:\"public\";i:1;s:12:\"\0*\0protected\";i:2;s:13:\"\0Test\0private\";i:3;}".as_slice(),
+                b"O:4:\"Test\":4:{s:6:\"public\";i:1;s:12:\"\0*\0protected\";i:2;s:13:\"\0Test\0private\";i:3;s:17:\"\0Parent\0inherited\";i:4;}".as_slice(),
             ),
             (
                 Value::ObjectReference(NonZeroUsize::new(42).unwrap()),
@@ -252,20 +593,35 @@ mod tests {
     fn encode_custom_object() {
         let cases = [(
             Value::CustomObject {
-                class_name: b"CustomSerializableClass".as_slice(),
-                data: b"foobar".as_slice(),
+                class_name: b"CustomSerializableClass".into(),
+                data: b"foobar".into(),
             },
             b"C:23:\"CustomSerializableClass\":6:{foobar}".as_slice(),
         )];
         run_encode_cases(&cases);
     }
 
+    #[test]
+    fn encode_enum() {
+        let cases = [(
+            Value::Enum {
+                class_name: b"App\\Status".into(),
+                case: b"Active".into(),
+            },
+            b"E:17:\"App\\Status:Active\";".as_slice(),
+        )];
+        run_encode_cases(&cases);
+    }
+
     #[test]
     fn encode_value_array() {
         let cases = [
             (Value::Array(vec![]), b"a:0:{}".as_slice()),
             (
-                Value::Array(vec![(ArrayKey::String(b"foo"), Value::String(b"bar"))]),
+                Value::Array(vec![(
+                    ArrayKey::String(b"foo".into()),
+                    Value::String(b"bar".into()),
+                )]),
                 b"a:1:{s:3:\"foo\";s:3:\"bar\";}".as_slice(),
             ),
             (
@@ -298,20 +654,20 @@ mod tests {
             (vec![], b"".as_slice()),
             (
                 vec![SessionEntry {
-                    key: b"foo",
-                    value: Value::Integer(42),
+                    key: b"foo".into(),
+                    value: Some(Value::Integer(42)),
                 }],
                 b"foo|i:42;".as_slice(),
             ),
             (
                 vec![
                     SessionEntry {
-                        key: b"foo",
-                        value: Value::Integer(42),
+                        key: b"foo".into(),
+                        value: Some(Value::Integer(42)),
                     },
                     SessionEntry {
-                        key: b"bar",
-                        value: Value::String(b"baz".as_slice()),
+                        key: b"bar".into(),
+                        value: Some(Value::String(b"baz".into())),
                     },
                 ],
                 b"foo|i:42;bar|s:3:\"baz\";".as_slice(),
@@ -319,16 +675,16 @@ mod tests {
             (
                 vec![
                     SessionEntry {
-                        key: b"foo",
-                        value: Value::Integer(42),
+                        key: b"foo".into(),
+                        value: Some(Value::Integer(42)),
                     },
                     SessionEntry {
-                        key: b"bar",
-                        value: Value::String(b"baz|qux".as_slice()),
+                        key: b"bar".into(),
+                        value: Some(Value::String(b"baz|qux".into())),
                     },
                     SessionEntry {
-                        key: b"pub",
-                        value: Value::Integer(1337),
+                        key: b"pub".into(),
+                        value: Some(Value::Integer(1337)),
                     },
                 ],
                 b"foo|i:42;bar|s:7:\"baz|qux\";pub|i:1337;".as_slice(),
@@ -336,4 +692,172 @@ mod tests {
         ];
         run_session_encode_cases(&cases);
     }
+
+    #[test]
+    fn encode_undefined_session_entries() {
+        let cases = [(
+            vec![
+                SessionEntry {
+                    key: b"foo".into(),
+                    value: None,
+                },
+                SessionEntry {
+                    key: b"bar!".into(),
+                    value: Some(Value::Integer(1)),
+                },
+            ],
+            b"!foo|bar!|i:1;".as_slice(),
+        )];
+        run_session_encode_cases(&cases);
+
+        for key in [b"foo|bar".as_slice(), b"!foo"] {
+            let session = [SessionEntry {
+                key: key.into(),
+                value: Some(Value::Null),
+            }];
+            assert!(matches!(
+                session_encode(&mut Vec::new(), &session),
+                Err(Error::InvalidSessionKey { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn encode_php_serialize_session() {
+        let options = EncodeOptions::new().with_session_format(SessionFormat::PhpSerialize);
+        let cases = [
+            (vec![], b"a:0:{}".as_slice()),
+            (
+                vec![
+                    SessionEntry {
+                        key: b"foo".into(),
+                        value: Some(Value::Integer(42)),
+                    },
+                    SessionEntry {
+                        key: b"bar|!".into(),
+                        value: Some(Value::String(b"baz".into())),
+                    },
+                ],
+                b"a:2:{s:3:\"foo\";i:42;s:5:\"bar|!\";s:3:\"baz\";}".as_slice(),
+            ),
+        ];
+        let mut buffer = Vec::<u8>::new();
+        for (input, expected) in cases {
+            buffer.clear();
+            let count = session_encode_with(&mut buffer, &input, &options).unwrap();
+            assert_eq!(buffer, expected);
+            assert_eq!(count, expected.len());
+        }
+    }
+
+    #[test]
+    fn encode_php_binary_session() {
+        let options = EncodeOptions::new().with_session_format(SessionFormat::PhpBinary);
+        let session = [
+            SessionEntry {
+                key: b"foo".into(),
+                value: Some(Value::Integer(42)),
+            },
+            SessionEntry {
+                key: b"gone".into(),
+                value: None,
+            },
+            SessionEntry {
+                key: vec![b'k'; 127].into(),
+                value: Some(Value::Null),
+            },
+        ];
+        let mut buffer = Vec::<u8>::new();
+        let count = session_encode_with(&mut buffer, &session, &options).unwrap();
+        let expected = [b"\x03fooi:42;\x84gone\x7f".as_slice(), &[b'k'; 127], b"N;"].concat();
+        assert_eq!(buffer, expected);
+        assert_eq!(count, expected.len());
+
+        let session = [SessionEntry {
+            key: vec![b'k'; 128].into(),
+            value: Some(Value::Null),
+        }];
+        assert!(matches!(
+            session_encode_with(&mut buffer, &session, &options),
+            Err(Error::InvalidSessionKey { key }) if key.len() == 128
+        ));
+    }
+
+    #[test]
+    fn encode_canonical_keys() {
+        let value = Value::Array(vec![
+            (ArrayKey::String(b"42".into()), Value::Null),
+            (ArrayKey::String(b"-0".into()), Value::Null),
+            (ArrayKey::String(b"9223372036854775808".into()), Value::Null),
+            (ArrayKey::Integer(1), Value::Null),
+        ]);
+        let mut buffer = Vec::new();
+        serialize(&mut buffer, &value).unwrap();
+        assert!(buffer.starts_with(b"a:4:{s:2:\"42\";N;"));
+
+        buffer.clear();
+        let options = EncodeOptions::new().with_canonical_keys(true);
+        serialize_with(&mut buffer, &value, &options).unwrap();
+        assert_eq!(
+            buffer,
+            b"a:4:{i:42;N;s:2:\"-0\";N;s:19:\"9223372036854775808\";N;i:1;N;}"
+        );
+
+        buffer.clear();
+        let session = [SessionEntry {
+            key: b"7".as_slice().into(),
+            value: Some(Value::Null),
+        }];
+        let options = options.with_session_format(SessionFormat::PhpSerialize);
+        session_encode_with(&mut buffer, &session, &options).unwrap();
+        assert_eq!(buffer, b"a:1:{i:7;N;}");
+    }
+
+    /// Writer accepting a single byte per call, like a congested socket.
+    struct Trickle(Vec<u8>);
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.extend(buf.iter().take(1));
+            Ok(buf.len().min(1))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn encode_through_partial_writes() {
+        let value = Value::Array(vec![
+            (
+                ArrayKey::Integer(0),
+                Value::String(b"a\0b".as_slice().into()),
+            ),
+            (
+                ArrayKey::String(b"d".as_slice().into()),
+                Value::Decimal(0.1),
+            ),
+        ]);
+        let options = EncodeOptions::new()
+            .with_escaped_strings(true)
+            .with_serialize_precision(Some(17));
+        let mut expected = Vec::new();
+        serialize_with(&mut expected, &value, &options).unwrap();
+
+        let mut writer = Trickle(Vec::new());
+        let count = serialize_with(&mut writer, &value, &options).unwrap();
+        assert_eq!(writer.0, expected);
+        assert_eq!(count, expected.len());
+        assert_eq!(serialized_len_with(&value, &options), expected.len());
+
+        let session = [SessionEntry {
+            key: b"k".as_slice().into(),
+            value: Some(value),
+        }];
+        let mut writer = Trickle(Vec::new());
+        let count = session_encode(&mut writer, &session).unwrap();
+        assert_eq!(count, writer.0.len());
+        assert!(writer.0.starts_with(b"k|a:2:{"));
+    }
 }
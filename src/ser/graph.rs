@@ -0,0 +1,278 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+
+use crate::{
+    error::Error,
+    reference::{Graph, GraphEntry, Node, NodeId},
+    value::{SessionFormat, Value},
+};
+
+use super::{put, serialize_with, write_key, write_property_name, write_session, EncodeOptions};
+
+fn is_object(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Object { .. } | Node::CustomObject { .. } | Node::Enum { .. }
+    )
+}
+
+fn invalid(id: NodeId, reason: &'static str) -> Error {
+    Error::InvalidGraph {
+        node: id.index(),
+        reason,
+    }
+}
+
+/// Numbers slots the way PHP's `serialize()` does, to write shared values as `r:` and `R:`.
+struct Encoder<'g, 'a> {
+    graph: &'g Graph<'a>,
+    options: &'g EncodeOptions,
+    /// Number of slots taken so far.
+    slots: usize,
+    /// Slot of every object and reference already written.
+    seen: HashMap<NodeId, usize>,
+    /// Arrays being written, which can't be met again other than through a reference.
+    open_arrays: HashSet<NodeId>,
+}
+
+impl<'g, 'a> Encoder<'g, 'a> {
+    fn new(graph: &'g Graph<'a>, options: &'g EncodeOptions) -> Self {
+        Self {
+            graph,
+            options,
+            slots: 0,
+            seen: HashMap::new(),
+            open_arrays: HashSet::new(),
+        }
+    }
+
+    fn node(&self, id: NodeId) -> Result<&'g Node<'a>, Error> {
+        self.graph
+            .get(id)
+            .ok_or_else(|| invalid(id, "node doesn't exist"))
+    }
+
+    fn write<W: Write>(&mut self, w: &mut W, id: NodeId) -> Result<usize, Error> {
+        self.slots += 1;
+        match self.node(id)? {
+            Node::Reference(target) => {
+                let node = self.node(*target)?;
+                // A reference to an object shares the slot of the object itself.
+                let key = if is_object(node) { *target } else { id };
+                if let Some(slot) = self.seen.get(&key) {
+                    // Unlike `r:`, `R:` doesn't take a slot.
+                    self.slots -= 1;
+                    return put(w, format!("R:{slot};").as_bytes());
+                }
+                self.seen.insert(key, self.slots);
+                self.write_node(w, *target, node)
+            }
+            node if is_object(node) => {
+                if let Some(slot) = self.seen.get(&id) {
+                    return put(w, format!("r:{slot};").as_bytes());
+                }
+                self.seen.insert(id, self.slots);
+                self.write_node(w, id, node)
+            }
+            node => self.write_node(w, id, node),
+        }
+    }
+
+    /// Write the content of a node whose slot is already taken.
+    fn write_node<W: Write>(
+        &mut self,
+        w: &mut W,
+        id: NodeId,
+        node: &'g Node<'a>,
+    ) -> Result<usize, Error> {
+        let value = match node {
+            Node::Array(items) => {
+                if !self.open_arrays.insert(id) {
+                    return Err(invalid(id, "array contains itself without a reference"));
+                }
+                let mut count = put(w, format!("a:{}:{{", items.len()).as_bytes())?;
+                for (key, value) in items {
+                    count += write_key(w, key, self.options)?;
+                    count += self.write(w, *value)?;
+                }
+                count += put(w, b"}")?;
+                self.open_arrays.remove(&id);
+                return Ok(count);
+            }
+            Node::Object {
+                class_name,
+                properties,
+            } => {
+                let mut count = put(w, format!("O:{}:\"", class_name.len()).as_bytes())?;
+                count += put(w, class_name)?;
+                count += put(w, format!("\":{}:{{", properties.len()).as_bytes())?;
+                for property in properties {
+                    count += write_property_name(w, &property.visibility, &property.name)?;
+                    count += self.write(w, property.value)?;
+                }
+                count += put(w, b"}")?;
+                return Ok(count);
+            }
+            Node::Reference(_) => return Err(invalid(id, "reference points to a reference")),
+            Node::Null => Value::Null,
+            Node::Boolean(b) => Value::Boolean(*b),
+            Node::Integer(n) => Value::Integer(*n),
+            Node::Decimal(d) => Value::Decimal(*d),
+            Node::String(s) => Value::String(Cow::Borrowed(s)),
+            Node::CustomObject { class_name, data } => Value::CustomObject {
+                class_name: Cow::Borrowed(class_name),
+                data: Cow::Borrowed(data),
+            },
+            Node::Enum { class_name, case } => Value::Enum {
+                class_name: Cow::Borrowed(class_name),
+                case: Cow::Borrowed(case),
+            },
+        };
+        serialize_with(w, &value, self.options)
+    }
+}
+
+/// Encode the value at `root` of a graph to PHP's `serialize` format.
+///
+/// Slots are numbered as PHP does: objects met again are written as `r:`, and
+/// [`Node::Reference`]s met again as `R:`, so that `unserialize()` restores the sharing.
+///
+/// ```
+/// use muesli::{serialize_graph, unserialize, EncodeOptions, Graph};
+///
+/// let input = b"a:3:{i:0;O:8:\"stdClass\":0:{}i:1;r:2;i:2;a:1:{i:0;R:4;}}";
+/// let (graph, root) = Graph::from_value(unserialize(input).unwrap()).unwrap();
+///
+/// let mut output = Vec::new();
+/// serialize_graph(&mut output, &graph, root, &EncodeOptions::new()).unwrap();
+/// assert_eq!(output, input);
+/// ```
+///
+/// # Errors
+///
+/// Will return `Err` if write fail, or if the graph has missing nodes, references to references,
+/// or arrays containing themselves other than through a reference.
+pub fn serialize_graph<W: Write>(
+    w: &mut W,
+    graph: &Graph,
+    root: NodeId,
+    options: &EncodeOptions,
+) -> Result<usize, Error> {
+    Encoder::new(graph, options).write(w, root)
+}
+
+/// Encode the entries of a graph to PHP's session format, see [`serialize_graph`].
+///
+/// Slots are numbered across all entries, as PHP does.
+///
+/// # Errors
+///
+/// Will return `Err` if write fail, if a key can't be written in the selected format, or if the
+/// graph can't be encoded.
+pub fn session_encode_graph<W: Write>(
+    w: &mut W,
+    graph: &Graph,
+    entries: &[GraphEntry],
+    options: &EncodeOptions,
+) -> Result<usize, Error> {
+    let mut encoder = Encoder::new(graph, options);
+    if options.session_format == SessionFormat::PhpSerialize {
+        // The array holding the whole session takes the first slot.
+        encoder.slots = 1;
+    }
+    write_session(
+        w,
+        entries
+            .iter()
+            .map(|entry| (entry.key.as_ref(), entry.value)),
+        options,
+        |w, value| encoder.write(w, value),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session_decode, unserialize, ArrayKey};
+
+    use super::*;
+
+    fn encode(graph: &Graph, root: NodeId) -> Result<Vec<u8>, Error> {
+        let mut buffer = Vec::new();
+        let count = serialize_graph(&mut buffer, graph, root, &EncodeOptions::new())?;
+        assert_eq!(count, buffer.len());
+        Ok(buffer)
+    }
+
+    #[test]
+    fn encode_graph_round_trip() {
+        let cases: &[&[u8]] = &[
+            b"i:1;",
+            b"a:2:{i:0;i:1;i:1;R:2;}",
+            b"a:1:{i:0;a:1:{i:0;R:2;}}",
+            b"a:3:{i:0;O:3:\"Foo\":1:{s:1:\"a\";i:1;}i:1;r:2;i:2;R:3;}",
+            b"a:3:{i:0;O:8:\"stdClass\":0:{}i:1;R:2;i:2;r:2;}",
+            b"O:3:\"Foo\":2:{s:4:\"\0*\0a\";r:1;s:6:\"\0Foo\0b\";R:1;}",
+            b"a:3:{i:0;E:7:\"Foo:Bar\";i:1;r:2;s:1:\"x\";C:3:\"Foo\":2:{ab}}",
+        ];
+        for input in cases {
+            let (graph, root) = Graph::from_value(unserialize(input).unwrap()).unwrap();
+            assert_eq!(encode(&graph, root).unwrap(), *input);
+        }
+    }
+
+    #[test]
+    fn encode_built_graph() {
+        let mut graph = Graph::new();
+        let integer = graph.insert(Node::Integer(1));
+        let reference = graph.insert(Node::Reference(integer));
+        let shared = graph.insert(Node::Array(vec![(ArrayKey::Integer(0), reference)]));
+        let root = graph.insert(Node::Array(vec![
+            (ArrayKey::Integer(0), shared),
+            (ArrayKey::String(b"a".into()), shared),
+        ]));
+        // Arrays are values in PHP, the shared one is written twice but keeps its reference.
+        assert_eq!(
+            encode(&graph, root).unwrap(),
+            b"a:2:{i:0;a:1:{i:0;i:1;}s:1:\"a\";a:1:{i:0;R:3;}}"
+        );
+
+        let cycle = graph.insert(Node::Null);
+        *graph.get_mut(cycle).unwrap() = Node::Array(vec![(ArrayKey::Integer(0), cycle)]);
+        assert!(matches!(
+            encode(&graph, cycle),
+            Err(Error::InvalidGraph { node, .. }) if node == cycle.index()
+        ));
+
+        let nested = graph.insert(Node::Reference(reference));
+        assert!(matches!(
+            encode(&graph, nested),
+            Err(Error::InvalidGraph { node, .. }) if node == reference.index()
+        ));
+
+        assert!(matches!(
+            encode(&Graph::new(), root),
+            Err(Error::InvalidGraph { node, .. }) if node == root.index()
+        ));
+    }
+
+    #[test]
+    fn encode_session_graph() {
+        let input = b"a|O:8:\"stdClass\":0:{}!b|c|r:1;d|R:1;e|a:1:{i:0;R:3;}";
+        let (graph, entries) = Graph::from_session(session_decode(input).unwrap()).unwrap();
+
+        let mut buffer = Vec::new();
+        session_encode_graph(&mut buffer, &graph, &entries, &EncodeOptions::new()).unwrap();
+        assert_eq!(buffer, input);
+
+        buffer.clear();
+        let options = EncodeOptions::new().with_session_format(SessionFormat::PhpSerialize);
+        session_encode_graph(&mut buffer, &graph, &entries, &options).unwrap();
+        assert_eq!(
+            buffer,
+            b"a:4:{s:1:\"a\";O:8:\"stdClass\":0:{}s:1:\"c\";r:2;s:1:\"d\";R:2;s:1:\"e\";a:1:{i:0;R:4;}}"
+        );
+    }
+}
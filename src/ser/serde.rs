@@ -0,0 +1,942 @@
+use std::{fmt, io::Write};
+
+use serde::ser::{
+    self, Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct,
+    SerializeStructVariant, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
+
+use crate::{
+    error::Error,
+    ser::EncodeOptions,
+    value::{canonical_integer, Value},
+};
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Prefix of the serde name of structs written as objects, the rest being their class name.
+const CLASS_PREFIX: &str = "php:";
+
+/// Serde serializer writing PHP's `serialize` format.
+///
+/// Structs are written as associative arrays keyed by field names, `a:1:{s:3:"foo";i:1;}`, unless
+/// their serde name starts with `php:`: `#[serde(rename = "php:App\\Foo")]` writes an object
+/// of class `App\Foo`, `O:7:"App\Foo":1:{s:3:"foo";i:1;}`. Fields of objects are public unless
+/// their name carries PHP's visibility mangling, e.g. `#[serde(rename = "\0*\0foo")]` for a
+/// protected property or `#[serde(rename = "\0App\\Foo\0foo")]` for a private one.
+pub struct Serializer<W> {
+    writer: W,
+    options: EncodeOptions,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            options: EncodeOptions::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_options(mut self, options: EncodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes).map_err(Error::Io)
+    }
+
+    fn write_value(&mut self, value: &Value) -> Result<()> {
+        crate::ser::serialize_with(&mut self.writer, value, &self.options)?;
+        Ok(())
+    }
+
+    fn write_variant_header(&mut self, variant: &str) -> Result<()> {
+        self.write(b"a:1:{")?;
+        self.write_value(&Value::String(variant.as_bytes().into()))
+    }
+
+    fn write_struct_header(&mut self, name: &str, len: usize) -> Result<()> {
+        match name.strip_prefix(CLASS_PREFIX) {
+            Some(class) => self.write(format!("O:{}:\"{class}\":{len}:{{", class.len()).as_bytes()),
+            None => self.write(format!("a:{len}:{{").as_bytes()),
+        }
+    }
+
+    fn compound(&mut self, len: Option<usize>, variant: bool) -> Result<Compound<'_, W>> {
+        let buffer = match len {
+            Some(len) => {
+                self.write(format!("a:{len}:{{").as_bytes())?;
+                None
+            }
+            None => Some(Serializer::new(Vec::new()).with_options(self.options.clone())),
+        };
+        Ok(Compound {
+            ser: self,
+            buffer,
+            declared: len,
+            count: 0,
+            variant,
+        })
+    }
+}
+
+/// Serialize `value` to PHP's `serialize` format into `writer`.
+///
+/// ```
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// #[serde(rename = "php:App\\User")]
+/// struct User<'a> {
+///     name: &'a str,
+///     #[serde(rename = "\0*\0roles")]
+///     roles: Vec<&'a str>,
+///     settings: Settings,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Settings {
+///     theme: &'static str,
+/// }
+///
+/// let user = User {
+///     name: "alice",
+///     roles: vec!["admin"],
+///     settings: Settings { theme: "dark" },
+/// };
+///
+/// let mut output = Vec::new();
+/// muesli::to_writer(&mut output, &user).unwrap();
+/// assert_eq!(
+///     output,
+///     b"O:8:\"App\\User\":3:{s:4:\"name\";s:5:\"alice\";s:8:\"\0*\0roles\";a:1:{i:0;s:5:\"admin\";}\
+///       s:8:\"settings\";a:1:{s:5:\"theme\";s:4:\"dark\";}}",
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Will return `Err` if write fail, if `value` can't be represented in PHP's format, or if it
+/// serializes a different number of elements than it declared.
+pub fn to_writer<W: Write, T: ?Sized + Serialize>(writer: W, value: &T) -> Result<()> {
+    value.serialize(&mut Serializer::new(writer))
+}
+
+/// Serialize `value` to PHP's `serialize` format into a new buffer.
+///
+/// # Errors
+///
+/// Will return `Err` if `value` can't be represented in PHP's format.
+pub fn to_vec<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    to_writer(&mut output, value)?;
+    Ok(output)
+}
+
+fn integer<T: TryInto<i64> + fmt::Display + Copy>(n: T) -> Result<i64> {
+    n.try_into()
+        .map_err(|_| Error::Message(format!("integer {n} is out of PHP's integer range")))
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, W>;
+    type SerializeTuple = Compound<'a, W>;
+    type SerializeTupleStruct = Compound<'a, W>;
+    type SerializeTupleVariant = Compound<'a, W>;
+    type SerializeMap = Compound<'a, W>;
+    type SerializeStruct = Compound<'a, W>;
+    type SerializeStructVariant = Compound<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.write_value(&Value::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.write_value(&Value::Integer(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.serialize_i64(integer(v)?)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.serialize_i64(integer(v)?)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.serialize_i64(integer(v)?)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.write_value(&Value::Decimal(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write_value(&Value::String(v.into()))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.write_value(&Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.write_variant_header(variant)?;
+        value.serialize(&mut *self)?;
+        self.write(b"}")
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a, W>> {
+        self.compound(len, false)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, W>> {
+        self.compound(Some(len), false)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, W>> {
+        self.compound(Some(len), false)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, W>> {
+        self.write_variant_header(variant)?;
+        self.compound(Some(len), true)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a, W>> {
+        self.compound(len, false)
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Compound<'a, W>> {
+        self.write_struct_header(name, len)?;
+        Ok(Compound {
+            ser: self,
+            buffer: None,
+            declared: Some(len),
+            count: 0,
+            variant: false,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, W>> {
+        self.write_variant_header(variant)?;
+        self.write(format!("a:{len}:{{").as_bytes())?;
+        Ok(Compound {
+            ser: self,
+            buffer: None,
+            declared: Some(len),
+            count: 0,
+            variant: true,
+        })
+    }
+}
+
+/// Array or object being written.
+///
+/// Entries of sequences and maps of unknown length are buffered until their count is known.
+pub struct Compound<'a, W> {
+    ser: &'a mut Serializer<W>,
+    buffer: Option<Serializer<Vec<u8>>>,
+    /// Length already written in the header, which the entries must match.
+    declared: Option<usize>,
+    count: usize,
+    variant: bool,
+}
+
+impl<W: Write> Compound<'_, W> {
+    fn key<K: ?Sized + Serialize>(&mut self, key: &K) -> Result<()> {
+        self.count += 1;
+        match &mut self.buffer {
+            Some(buffer) => key.serialize(KeySerializer(buffer)),
+            None => key.serialize(KeySerializer(&mut *self.ser)),
+        }
+    }
+
+    fn value<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<()> {
+        match &mut self.buffer {
+            Some(buffer) => value.serialize(buffer),
+            None => value.serialize(&mut *self.ser),
+        }
+    }
+
+    fn element<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<()> {
+        let index = self.count;
+        self.key(&index)?;
+        self.value(value)
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Some(declared) = self.declared.filter(|&declared| declared != self.count) {
+            return Err(Error::Message(format!(
+                "declared {declared} elements but serialized {}",
+                self.count
+            )));
+        }
+        if let Some(buffer) = self.buffer {
+            self.ser.write(format!("a:{}:{{", self.count).as_bytes())?;
+            self.ser.write(&buffer.writer)?;
+        }
+        self.ser.write(b"}")?;
+        if self.variant {
+            self.ser.write(b"}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> SerializeSeq for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> SerializeTuple for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> SerializeTupleStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> SerializeTupleVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> SerializeMap for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        self.key(key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.value(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> SerializeStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.key(key)?;
+        self.value(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> SerializeStructVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.key(key)?;
+        self.value(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+/// PHP array keys are either integers or strings.
+struct KeySerializer<'a, W>(&'a mut Serializer<W>);
+
+fn key_must_be_integer_or_string() -> Error {
+    Error::Message("array key must be an integer or a string".to_string())
+}
+
+impl<W: Write> ser::Serializer for KeySerializer<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.0.serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.serialize_i64(integer(v)?)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.serialize_i64(integer(v)?)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.serialize_i64(integer(v)?)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        match canonical_integer(v).filter(|_| self.0.options.canonical_keys) {
+            Some(n) => self.0.serialize_i64(n),
+            None => self.0.serialize_bytes(v),
+        }
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.0.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_integer_or_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_integer_or_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    #[serde(rename = "php:Shop\\Item")]
+    struct Item<'a> {
+        sku: &'a str,
+        #[serde(rename = "\0*\0quantity")]
+        quantity: u32,
+        #[serde(rename = "\0Shop\\Item\0price")]
+        price: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<&'a str>,
+    }
+
+    #[derive(Serialize)]
+    enum Status {
+        Active,
+        Renamed(&'static str),
+        Suspended { until: i64 },
+        Pair(i64, i64),
+    }
+
+    #[test]
+    fn serialize_scalars() {
+        assert_eq!(to_vec(&()).unwrap(), b"N;");
+        assert_eq!(to_vec(&None::<i64>).unwrap(), b"N;");
+        assert_eq!(to_vec(&Some(3u8)).unwrap(), b"i:3;");
+        assert_eq!(to_vec(&true).unwrap(), b"b:1;");
+        assert_eq!(to_vec(&-42i32).unwrap(), b"i:-42;");
+        assert_eq!(to_vec(&0.5f64).unwrap(), b"d:0.5;");
+        assert_eq!(to_vec(&'é').unwrap(), "s:2:\"é\";".as_bytes());
+        assert_eq!(to_vec("foo").unwrap(), b"s:3:\"foo\";");
+        assert!(to_vec(&u64::MAX).is_err());
+    }
+
+    #[test]
+    fn serialize_sequences() {
+        assert_eq!(to_vec(&Vec::<i64>::new()).unwrap(), b"a:0:{}");
+        assert_eq!(
+            to_vec(&vec!["a", "b"]).unwrap(),
+            b"a:2:{i:0;s:1:\"a\";i:1;s:1:\"b\";}"
+        );
+        assert_eq!(to_vec(&(1, "a")).unwrap(), b"a:2:{i:0;i:1;i:1;s:1:\"a\";}");
+        assert_eq!(
+            to_vec(&vec![vec![1], vec![]]).unwrap(),
+            b"a:2:{i:0;a:1:{i:0;i:1;}i:1;a:0:{}}"
+        );
+    }
+
+    #[test]
+    fn serialize_sequence_of_unknown_length() {
+        struct Odd(u8);
+
+        impl Serialize for Odd {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer.collect_seq((0..self.0).filter(|n| n % 2 == 1))
+            }
+        }
+
+        assert_eq!(to_vec(&Odd(6)).unwrap(), b"a:3:{i:0;i:1;i:1;i:3;i:2;i:5;}");
+    }
+
+    #[test]
+    fn reject_wrong_declared_length() {
+        struct Short;
+
+        impl Serialize for Short {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                let mut seq = serializer.serialize_seq(Some(2))?;
+                seq.serialize_element(&1)?;
+                seq.end()
+            }
+        }
+
+        struct Long;
+
+        impl Serialize for Long {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                let mut object = serializer.serialize_struct("php:Foo", 0)?;
+                object.serialize_field("x", &1)?;
+                object.end()
+            }
+        }
+
+        assert!(matches!(to_vec(&Short), Err(Error::Message(_))));
+        assert!(matches!(to_vec(&Long), Err(Error::Message(_))));
+    }
+
+    #[test]
+    fn serialize_maps() {
+        let map = BTreeMap::from([(3, "three"), (7, "seven")]);
+        assert_eq!(
+            to_vec(&map).unwrap(),
+            b"a:2:{i:3;s:5:\"three\";i:7;s:5:\"seven\";}"
+        );
+
+        let map = BTreeMap::from([("foo", true)]);
+        assert_eq!(to_vec(&map).unwrap(), b"a:1:{s:3:\"foo\";b:1;}");
+
+        let map = BTreeMap::from([(vec![1], true)]);
+        assert!(to_vec(&map).is_err());
+    }
+
+    #[test]
+    fn serialize_structs() {
+        #[derive(Serialize)]
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        assert_eq!(
+            to_vec(&Point { x: 1, y: -2 }).unwrap(),
+            b"a:2:{s:1:\"x\";i:1;s:1:\"y\";i:-2;}"
+        );
+        let item = Item {
+            sku: "A-42",
+            quantity: 2,
+            price: 9.5,
+            note: None,
+        };
+        assert_eq!(
+            to_vec(&item).unwrap(),
+            b"O:9:\"Shop\\Item\":3:{s:3:\"sku\";s:4:\"A-42\";s:11:\"\0*\0quantity\";i:2;\
+              s:16:\"\0Shop\\Item\0price\";d:9.5;}"
+        );
+    }
+
+    #[test]
+    fn serialize_objects_holding_arrays() {
+        #[derive(Serialize)]
+        struct Address<'a> {
+            city: &'a str,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename = "php:Shop\\Order")]
+        struct Order<'a> {
+            #[serde(rename = "\0*\0address")]
+            address: Address<'a>,
+            items: Vec<Item<'a>>,
+            status: Status,
+        }
+
+        let order = Order {
+            address: Address { city: "Lyon" },
+            items: vec![Item {
+                sku: "B-7",
+                quantity: 1,
+                price: 3.5,
+                note: Some("gift"),
+            }],
+            status: Status::Suspended { until: 10 },
+        };
+        assert_eq!(
+            to_vec(&order).unwrap(),
+            b"O:10:\"Shop\\Order\":3:{\
+              s:10:\"\0*\0address\";a:1:{s:4:\"city\";s:4:\"Lyon\";}\
+              s:5:\"items\";a:1:{i:0;O:9:\"Shop\\Item\":4:{s:3:\"sku\";s:3:\"B-7\";\
+              s:11:\"\0*\0quantity\";i:1;s:16:\"\0Shop\\Item\0price\";d:3.5;\
+              s:4:\"note\";s:4:\"gift\";}}\
+              s:6:\"status\";a:1:{s:9:\"Suspended\";a:1:{s:5:\"until\";i:10;}}}"
+        );
+    }
+
+    #[test]
+    fn serialize_enums() {
+        let cases = [
+            (Status::Active, b"s:6:\"Active\";".as_slice()),
+            (
+                Status::Renamed("foo"),
+                b"a:1:{s:7:\"Renamed\";s:3:\"foo\";}".as_slice(),
+            ),
+            (
+                Status::Suspended { until: 10 },
+                b"a:1:{s:9:\"Suspended\";a:1:{s:5:\"until\";i:10;}}".as_slice(),
+            ),
+            (
+                Status::Pair(1, 2),
+                b"a:1:{s:4:\"Pair\";a:2:{i:0;i:1;i:1;i:2;}}".as_slice(),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(to_vec(&input).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn serialize_with_options() {
+        let mut serializer = Serializer::new(Vec::new())
+            .with_options(EncodeOptions::new().with_escaped_strings(true));
+        BTreeMap::from([("a\tb", "\0")])
+            .serialize(&mut serializer)
+            .unwrap();
+        assert_eq!(
+            serializer.into_inner(),
+            b"a:1:{S:3:\"a\\09b\";S:1:\"\\00\";}"
+        );
+    }
+
+    #[test]
+    fn serialize_canonical_keys() {
+        let mut serializer = Serializer::new(Vec::new())
+            .with_options(EncodeOptions::new().with_canonical_keys(true));
+        BTreeMap::from([("42", 'a'), ("x", '7')])
+            .serialize(&mut serializer)
+            .unwrap();
+        assert_eq!(
+            serializer.into_inner(),
+            b"a:2:{i:42;s:1:\"a\";s:1:\"x\";s:1:\"7\";}"
+        );
+
+        let mut serializer = Serializer::new(Vec::new())
+            .with_options(EncodeOptions::new().with_canonical_keys(true));
+        BTreeMap::from([('3', true)])
+            .serialize(&mut serializer)
+            .unwrap();
+        assert_eq!(serializer.into_inner(), b"a:1:{i:3;b:1;}");
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Cart {
+            items: Vec<(String, u32)>,
+            coupon: Option<String>,
+            total: f64,
+        }
+
+        let cart = Cart {
+            items: vec![("A-42".to_string(), 2), ("B-7".to_string(), 1)],
+            coupon: None,
+            total: 21.25,
+        };
+
+        let output = to_vec(&cart).unwrap();
+        assert_eq!(crate::from_bytes::<Cart>(&output).unwrap(), cart);
+    }
+
+    #[test]
+    fn serialize_object_round_trip() {
+        #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+        #[serde(rename = "php:App\\Account")]
+        struct Account {
+            id: i64,
+            #[serde(rename = "\0*\0owner")]
+            owner: String,
+            #[serde(rename = "\0App\\Account\0balance")]
+            balance: f64,
+        }
+
+        let account = Account {
+            id: 7,
+            owner: "alice".to_string(),
+            balance: 12.5,
+        };
+
+        let output = to_vec(&account).unwrap();
+        assert_eq!(
+            output,
+            b"O:11:\"App\\Account\":3:{s:2:\"id\";i:7;s:8:\"\0*\0owner\";s:5:\"alice\";\
+              s:20:\"\0App\\Account\0balance\";d:12.5;}"
+        );
+        assert_eq!(crate::from_bytes::<Account>(&output).unwrap(), account);
+    }
+}
@@ -1,4 +1,9 @@
-use std::num::NonZeroUsize;
+use std::{borrow::Cow, num::NonZeroUsize, ops::Index};
+
+mod array;
+mod juggle;
+
+pub use array::PhpArray;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value<'a> {
@@ -6,42 +11,522 @@ pub enum Value<'a> {
     Boolean(bool),
     Integer(i64),
     Decimal(f64),
-    String(&'a [u8]),
+    String(Cow<'a, [u8]>),
     Array(Vec<(ArrayKey<'a>, Value<'a>)>),
     Object {
-        class_name: &'a [u8],
+        class_name: Cow<'a, [u8]>,
         properties: Vec<ObjectProperty<'a>>,
     },
     CustomObject {
-        class_name: &'a [u8],
-        data: &'a [u8],
+        class_name: Cow<'a, [u8]>,
+        data: Cow<'a, [u8]>,
+    },
+    Enum {
+        class_name: Cow<'a, [u8]>,
+        case: Cow<'a, [u8]>,
     },
     ValueReference(NonZeroUsize),
     ObjectReference(NonZeroUsize),
 }
 
+/// [`Value`] that doesn't borrow from the input it was decoded from.
+pub type OwnedValue = Value<'static>;
+
+fn owned(bytes: Cow<'_, [u8]>) -> Cow<'static, [u8]> {
+    Cow::Owned(bytes.into_owned())
+}
+
+impl Value<'_> {
+    /// Copy all borrowed data, so the value no longer depends on the input's lifetime.
+    #[must_use]
+    pub fn into_owned(self) -> OwnedValue {
+        match self {
+            Value::Null => Value::Null,
+            Value::Boolean(b) => Value::Boolean(b),
+            Value::Integer(n) => Value::Integer(n),
+            Value::Decimal(d) => Value::Decimal(d),
+            Value::String(s) => Value::String(owned(s)),
+            Value::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect(),
+            ),
+            Value::Object {
+                class_name,
+                properties,
+            } => Value::Object {
+                class_name: owned(class_name),
+                properties: properties
+                    .into_iter()
+                    .map(ObjectProperty::into_owned)
+                    .collect(),
+            },
+            Value::CustomObject { class_name, data } => Value::CustomObject {
+                class_name: owned(class_name),
+                data: owned(data),
+            },
+            Value::Enum { class_name, case } => Value::Enum {
+                class_name: owned(class_name),
+                case: owned(case),
+            },
+            Value::ValueReference(idx) => Value::ValueReference(idx),
+            Value::ObjectReference(idx) => Value::ObjectReference(idx),
+        }
+    }
+
+    /// Create a value borrowing all its data from `self`.
+    #[must_use]
+    pub fn as_borrowed(&self) -> Value<'_> {
+        match self {
+            Value::Null => Value::Null,
+            Value::Boolean(b) => Value::Boolean(*b),
+            Value::Integer(n) => Value::Integer(*n),
+            Value::Decimal(d) => Value::Decimal(*d),
+            Value::String(s) => Value::String(Cow::Borrowed(s)),
+            Value::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|(key, value)| (key.as_borrowed(), value.as_borrowed()))
+                    .collect(),
+            ),
+            Value::Object {
+                class_name,
+                properties,
+            } => Value::Object {
+                class_name: Cow::Borrowed(class_name),
+                properties: properties.iter().map(ObjectProperty::as_borrowed).collect(),
+            },
+            Value::CustomObject { class_name, data } => Value::CustomObject {
+                class_name: Cow::Borrowed(class_name),
+                data: Cow::Borrowed(data),
+            },
+            Value::Enum { class_name, case } => Value::Enum {
+                class_name: Cow::Borrowed(class_name),
+                case: Cow::Borrowed(case),
+            },
+            Value::ValueReference(idx) => Value::ValueReference(*idx),
+            Value::ObjectReference(idx) => Value::ObjectReference(*idx),
+        }
+    }
+}
+
+/// Integer a string key stands for, when PHP would store it as an integer key: an optional `-`
+/// followed by digits without leading zeros, within `i64`'s range, and other than `-0`.
+pub(crate) fn canonical_integer(key: &[u8]) -> Option<i64> {
+    let digits = key.strip_prefix(b"-").unwrap_or(key);
+    match digits {
+        [b'0'] if digits.len() == key.len() => Some(0),
+        [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit) => {
+            std::str::from_utf8(key).ok()?.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Bytes of a string.
+    #[must_use]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Content of a string, if it's valid UTF-8.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes()
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+    }
+
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Value of a float, or of an integer as a float.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Decimal(d) => Some(*d),
+            Value::Integer(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Element of an array, or property of an object, by name.
+    ///
+    /// Keys are looked up as PHP does, so `"1"` finds the element with the integer key `1`. When
+    /// a name appears several times, the last one wins, as in `unserialize()`.
+    #[must_use]
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&Value<'a>> {
+        let key = key.as_ref();
+        match (self, canonical_integer(key)) {
+            (Value::Array(_), Some(index)) => self.get_index(index),
+            (Value::Array(items), None) => items
+                .iter()
+                .rev()
+                .find(|(k, _)| matches!(k, ArrayKey::String(k) if **k == *key))
+                .map(|(_, value)| value),
+            (Value::Object { properties, .. }, _) => properties
+                .iter()
+                .rev()
+                .find(|property| *property.name == *key)
+                .map(|property| &property.value),
+            _ => None,
+        }
+    }
+
+    /// Element of an array by integer key, also found under its string form, e.g. `"1"`.
+    #[must_use]
+    pub fn get_index(&self, index: i64) -> Option<&Value<'a>> {
+        match self {
+            Value::Array(items) => items
+                .iter()
+                .rev()
+                .find(|(key, _)| match key {
+                    ArrayKey::Integer(key) => *key == index,
+                    ArrayKey::String(key) => canonical_integer(key) == Some(index),
+                })
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+static NULL: Value<'static> = Value::Null;
+
+/// Like [`Value::get`], but gives [`Value::Null`] for missing elements, as PHP does.
+impl<'a> Index<&str> for Value<'a> {
+    type Output = Value<'a>;
+
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+/// Like [`Value::get_index`], but gives [`Value::Null`] for missing elements, as PHP does.
+impl<'a> Index<i64> for Value<'a> {
+    type Output = Value<'a>;
+
+    fn index(&self, index: i64) -> &Self::Output {
+        self.get_index(index).unwrap_or(&NULL)
+    }
+}
+
+macro_rules! from_integer {
+    ($($target:ident: $($integer:ty),+;)+) => {
+        $($(
+            impl From<$integer> for $target<'_> {
+                fn from(n: $integer) -> Self {
+                    $target::Integer(i64::from(n))
+                }
+            }
+        )+)+
+    };
+}
+
+from_integer! {
+    Value: i8, i16, i32, i64, u8, u16, u32;
+    ArrayKey: i8, i16, i32, i64, u8, u16, u32;
+}
+
+macro_rules! from_bytes {
+    ($($target:ident;)+) => {
+        $(
+            impl<'a> From<&'a [u8]> for $target<'a> {
+                fn from(bytes: &'a [u8]) -> Self {
+                    $target::String(Cow::Borrowed(bytes))
+                }
+            }
+
+            impl<'a, const N: usize> From<&'a [u8; N]> for $target<'a> {
+                fn from(bytes: &'a [u8; N]) -> Self {
+                    $target::String(Cow::Borrowed(bytes))
+                }
+            }
+
+            impl<'a> From<&'a str> for $target<'a> {
+                fn from(string: &'a str) -> Self {
+                    $target::String(Cow::Borrowed(string.as_bytes()))
+                }
+            }
+
+            impl From<Vec<u8>> for $target<'_> {
+                fn from(bytes: Vec<u8>) -> Self {
+                    $target::String(Cow::Owned(bytes))
+                }
+            }
+
+            impl From<String> for $target<'_> {
+                fn from(string: String) -> Self {
+                    $target::String(Cow::Owned(string.into_bytes()))
+                }
+            }
+        )+
+    };
+}
+
+from_bytes! {
+    Value;
+    ArrayKey;
+}
+
+impl From<bool> for Value<'_> {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl From<f32> for Value<'_> {
+    fn from(d: f32) -> Self {
+        Value::Decimal(f64::from(d))
+    }
+}
+
+impl From<f64> for Value<'_> {
+    fn from(d: f64) -> Self {
+        Value::Decimal(d)
+    }
+}
+
+/// `None` is `null`.
+impl<'a, T: Into<Value<'a>>> From<Option<T>> for Value<'a> {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Build an array as PHP does for an array literal, see [`php!`](crate::php) and [`PhpArray`].
+///
+/// # Panics
+///
+/// Will panic if an element without a key follows the key `i64::MAX`, as PHP would fail.
+#[doc(hidden)]
+#[must_use]
+pub fn php_array<'a>(elements: Vec<(Option<ArrayKey<'a>>, Value<'a>)>) -> Value<'a> {
+    let mut array = PhpArray::with_capacity(elements.len());
+    for (key, value) in elements {
+        match key {
+            Some(key) => {
+                array.insert(key, value);
+            }
+            None => {
+                array.push(value);
+            }
+        }
+    }
+    array.into()
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum ArrayKey<'a> {
     Integer(i64),
-    String(&'a [u8]),
+    String(Cow<'a, [u8]>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum ObjectPropertyVisibility {
+impl ArrayKey<'_> {
+    #[must_use]
+    pub fn into_owned(self) -> ArrayKey<'static> {
+        match self {
+            ArrayKey::Integer(n) => ArrayKey::Integer(n),
+            ArrayKey::String(s) => ArrayKey::String(owned(s)),
+        }
+    }
+
+    #[must_use]
+    pub fn as_borrowed(&self) -> ArrayKey<'_> {
+        match self {
+            ArrayKey::Integer(n) => ArrayKey::Integer(*n),
+            ArrayKey::String(s) => ArrayKey::String(Cow::Borrowed(s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectPropertyVisibility<'a> {
     Public,
     Protected,
-    Private,
+    /// Private property, along with the class declaring it, which may be a parent of the object's
+    /// class.
+    Private(Cow<'a, [u8]>),
+}
+
+impl ObjectPropertyVisibility<'_> {
+    #[must_use]
+    pub fn into_owned(self) -> ObjectPropertyVisibility<'static> {
+        match self {
+            ObjectPropertyVisibility::Public => ObjectPropertyVisibility::Public,
+            ObjectPropertyVisibility::Protected => ObjectPropertyVisibility::Protected,
+            ObjectPropertyVisibility::Private(class_name) => {
+                ObjectPropertyVisibility::Private(owned(class_name))
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn as_borrowed(&self) -> ObjectPropertyVisibility<'_> {
+        match self {
+            ObjectPropertyVisibility::Public => ObjectPropertyVisibility::Public,
+            ObjectPropertyVisibility::Protected => ObjectPropertyVisibility::Protected,
+            ObjectPropertyVisibility::Private(class_name) => {
+                ObjectPropertyVisibility::Private(Cow::Borrowed(class_name))
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ObjectProperty<'a> {
-    pub visibility: ObjectPropertyVisibility,
-    pub name: &'a [u8],
+    pub visibility: ObjectPropertyVisibility<'a>,
+    pub name: Cow<'a, [u8]>,
     pub value: Value<'a>,
 }
 
+impl ObjectProperty<'_> {
+    #[must_use]
+    pub fn into_owned(self) -> ObjectProperty<'static> {
+        ObjectProperty {
+            visibility: self.visibility.into_owned(),
+            name: owned(self.name),
+            value: self.value.into_owned(),
+        }
+    }
+
+    #[must_use]
+    pub fn as_borrowed(&self) -> ObjectProperty<'_> {
+        ObjectProperty {
+            visibility: self.visibility.as_borrowed(),
+            name: Cow::Borrowed(&self.name),
+            value: self.value.as_borrowed(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SessionEntry<'a> {
-    pub key: &'a [u8],
-    pub value: Value<'a>,
+    pub key: Cow<'a, [u8]>,
+    /// `None` for variables that were undefined when the session was written.
+    pub value: Option<Value<'a>>,
+}
+
+impl SessionEntry<'_> {
+    #[must_use]
+    pub fn into_owned(self) -> SessionEntry<'static> {
+        SessionEntry {
+            key: owned(self.key),
+            value: self.value.map(Value::into_owned),
+        }
+    }
+
+    #[must_use]
+    pub fn as_borrowed(&self) -> SessionEntry<'_> {
+        SessionEntry {
+            key: Cow::Borrowed(&self.key),
+            value: self.value.as_ref().map(Value::as_borrowed),
+        }
+    }
+}
+
+/// Format of session data, as set by PHP's `session.serialize_handler`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionFormat {
+    /// `php` handler, PHP's default: `key|value` entries, e.g. `foo|i:42;`.
+    #[default]
+    Php,
+    /// `php_serialize` handler: the whole session as a single serialized array, e.g.
+    /// `a:1:{s:3:"foo";i:42;}`.
+    PhpSerialize,
+    /// `php_binary` handler: entries made of a byte holding the key length, the key, and the
+    /// value, e.g. `\x03fooi:42;`. Keys are limited to 127 bytes, the high bit of the length byte
+    /// marking undefined variables, which have no value.
+    PhpBinary,
+}
+
+/// How decoding handles string array keys that PHP would store as integers, like `s:2:"42";`.
+///
+/// PHP turns keys made of an optional `-` and digits without leading zeros, within `i64`'s range,
+/// into integers, so `serialize()` never writes them as strings. `"042"`, `"+1"`, and `"-0"` stay
+/// strings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumericKeys {
+    /// Keep them as [`ArrayKey::String`].
+    #[default]
+    Keep,
+    /// Turn them into [`ArrayKey::Integer`], as PHP's `unserialize()` does.
+    Canonicalize,
+    /// Fail with [`Error::NumericStringKey`](crate::Error::NumericStringKey) at the first one, to
+    /// find input PHP didn't write. [`validate`](crate::validate) lists all of them in
+    /// [`Stats::numeric_string_keys`](crate::Stats::numeric_string_keys).
+    Reject,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_integer_keys() {
+        let cases: [(&[u8], Option<i64>); 10] = [
+            (b"0", Some(0)),
+            (b"42", Some(42)),
+            (b"-7", Some(-7)),
+            (b"9223372036854775807", Some(i64::MAX)),
+            (b"9223372036854775808", None),
+            (b"-0", None),
+            (b"007", None),
+            (b"+1", None),
+            (b" 1", None),
+            (b"", None),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(canonical_integer(key), expected);
+        }
+    }
+
+    #[test]
+    fn access_values() {
+        let array = Value::Array(vec![
+            (ArrayKey::Integer(0), Value::String(b"zero".into())),
+            (ArrayKey::String(b"5".into()), Value::Integer(5)),
+            (ArrayKey::String(b"a".into()), Value::Decimal(1.5)),
+            (ArrayKey::String(b"a".into()), Value::Boolean(true)),
+            (ArrayKey::String(b"07".into()), Value::Null),
+        ]);
+        assert_eq!(array[0].as_str(), Some("zero"));
+        assert_eq!(array["0"].as_bytes(), Some(b"zero".as_slice()));
+        assert_eq!(array[5].as_i64(), Some(5));
+        assert_eq!(array["a"].as_bool(), Some(true));
+        assert_eq!(array.get("07"), Some(&Value::Null));
+        assert_eq!(array.get_index(7), None);
+        assert_eq!(array["missing"], Value::Null);
+        assert_eq!(array[5].as_f64(), Some(5.0));
+        assert_eq!(array[0].as_i64(), None);
+        assert_eq!(Value::String(b"\xff".into()).as_str(), None);
+
+        let object = Value::Object {
+            class_name: b"Foo".into(),
+            properties: vec![ObjectProperty {
+                visibility: ObjectPropertyVisibility::Private(b"Foo".into()),
+                name: b"bar".into(),
+                value: array,
+            }],
+        };
+        assert_eq!(object["bar"]["a"].as_bool(), Some(true));
+        assert_eq!(object.get_index(0), None);
+    }
 }
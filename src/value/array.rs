@@ -0,0 +1,317 @@
+use std::{borrow::Cow, collections::HashMap, slice, vec};
+
+use super::{canonical_integer, ArrayKey, Value};
+
+type Item<'a> = (ArrayKey<'a>, Value<'a>);
+
+/// Key as PHP stores it, numeric strings like `"42"` becoming integers.
+fn canonical(key: ArrayKey<'_>) -> ArrayKey<'_> {
+    match key {
+        ArrayKey::String(Cow::Borrowed(key)) => {
+            canonical_integer(key).map_or(ArrayKey::String(Cow::Borrowed(key)), ArrayKey::Integer)
+        }
+        ArrayKey::String(Cow::Owned(key)) => {
+            canonical_integer(&key).map_or(ArrayKey::String(Cow::Owned(key)), ArrayKey::Integer)
+        }
+        key => key,
+    }
+}
+
+/// Ordered map behaving like a PHP array.
+///
+/// Numeric string keys are stored as integers, inserting an existing key replaces its value in
+/// place, and [`PhpArray::push`] appends with the key following the greatest integer key used so
+/// far. Lookups don't depend on the array's length.
+///
+/// ```
+/// use muesli::{unserialize, ArrayKey, PhpArray, Value};
+///
+/// let value = unserialize(b"a:2:{i:0;s:1:\"a\";i:5;s:1:\"b\";}").unwrap();
+/// let mut array = PhpArray::try_from(value).unwrap();
+/// assert_eq!(array.get("5"), Some(&Value::String(b"b".into())));
+///
+/// assert_eq!(array.push(Value::Null), 6);
+/// assert!(!array.is_list());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PhpArray<'a> {
+    items: Vec<Item<'a>>,
+    /// Position of every key in `items`.
+    index: HashMap<ArrayKey<'a>, usize>,
+    /// Key [`PhpArray::push`] will use, which may be one past `i64::MAX`.
+    next: Option<i128>,
+}
+
+impl<'a> PhpArray<'a> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+            index: HashMap::with_capacity(capacity),
+            next: None,
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn position<'k>(&self, key: impl Into<ArrayKey<'k>>) -> Option<usize> {
+        self.index.get(&canonical(key.into())).copied()
+    }
+
+    #[must_use]
+    pub fn get<'k>(&self, key: impl Into<ArrayKey<'k>>) -> Option<&Value<'a>> {
+        self.position(key).map(|position| &self.items[position].1)
+    }
+
+    #[must_use]
+    pub fn get_mut<'k>(&mut self, key: impl Into<ArrayKey<'k>>) -> Option<&mut Value<'a>> {
+        self.position(key)
+            .map(|position| &mut self.items[position].1)
+    }
+
+    #[must_use]
+    pub fn contains_key<'k>(&self, key: impl Into<ArrayKey<'k>>) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Set the value of a key, returning the value it replaced.
+    ///
+    /// A new key is appended, while an existing one keeps its position.
+    pub fn insert(&mut self, key: impl Into<ArrayKey<'a>>, value: Value<'a>) -> Option<Value<'a>> {
+        let key = canonical(key.into());
+        if let ArrayKey::Integer(n) = key {
+            let after = i128::from(n) + 1;
+            self.next = Some(self.next.map_or(after, |next| next.max(after)));
+        }
+        if let Some(&position) = self.index.get(&key) {
+            return Some(std::mem::replace(&mut self.items[position].1, value));
+        }
+        self.index.insert(key.clone(), self.items.len());
+        self.items.push((key, value));
+        None
+    }
+
+    /// Append a value as `$array[] = $value` does, returning its key.
+    ///
+    /// # Errors
+    ///
+    /// Will give the value back if the array holds the key `i64::MAX`, leaving no key to append
+    /// with, where PHP would fail.
+    pub fn try_push(&mut self, value: Value<'a>) -> Result<i64, Value<'a>> {
+        let Ok(key) = i64::try_from(self.next.unwrap_or(0)) else {
+            return Err(value);
+        };
+        self.insert(key, value);
+        Ok(key)
+    }
+
+    /// Append a value as `$array[] = $value` does, returning its key.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the array holds the key `i64::MAX`, see [`PhpArray::try_push`] to handle
+    /// arrays from untrusted input.
+    pub fn push(&mut self, value: Value<'a>) -> i64 {
+        self.try_push(value)
+            .unwrap_or_else(|_| panic!("next array element is already occupied"))
+    }
+
+    /// Remove a key, shifting the elements after it, and return its value.
+    ///
+    /// As in PHP, keys given by [`PhpArray::push`] keep increasing.
+    pub fn remove<'k>(&mut self, key: impl Into<ArrayKey<'k>>) -> Option<Value<'a>> {
+        let position = self.position(key)?;
+        let (key, value) = self.items.remove(position);
+        self.index.remove(&key);
+        for (key, _) in &self.items[position..] {
+            if let Some(position) = self.index.get_mut(key) {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    /// Whether keys are `0`, `1`, `2`, … in order, as `array_is_list()` checks.
+    #[must_use]
+    pub fn is_list(&self) -> bool {
+        self.items
+            .iter()
+            .zip(0..)
+            .all(|((key, _), n)| *key == ArrayKey::Integer(n))
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Item<'a>> {
+        self.items.iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &ArrayKey<'a>> {
+        self.items.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value<'a>> {
+        self.items.iter().map(|(_, value)| value)
+    }
+
+    #[must_use]
+    pub fn as_slice(&self) -> &[Item<'a>] {
+        &self.items
+    }
+}
+
+/// Arrays are equal when they hold the same elements in the same order.
+impl PartialEq for PhpArray<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+/// Insert elements in order, a key given again replacing the value of the first one.
+impl<'a, K: Into<ArrayKey<'a>>> FromIterator<(K, Value<'a>)> for PhpArray<'a> {
+    fn from_iter<I: IntoIterator<Item = (K, Value<'a>)>>(iter: I) -> Self {
+        let mut array = Self::new();
+        array.extend(iter);
+        array
+    }
+}
+
+impl<'a, K: Into<ArrayKey<'a>>> Extend<(K, Value<'a>)> for PhpArray<'a> {
+    fn extend<I: IntoIterator<Item = (K, Value<'a>)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a> IntoIterator for PhpArray<'a> {
+    type Item = Item<'a>;
+    type IntoIter = vec::IntoIter<Item<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'s, 'a> IntoIterator for &'s PhpArray<'a> {
+    type Item = &'s Item<'a>;
+    type IntoIter = slice::Iter<'s, Item<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<'a> From<PhpArray<'a>> for Value<'a> {
+    fn from(array: PhpArray<'a>) -> Self {
+        Value::Array(array.items)
+    }
+}
+
+/// Read an array, e.g. as decoded by [`unserialize`](crate::unserialize), giving back any other
+/// value.
+impl<'a> TryFrom<Value<'a>> for PhpArray<'a> {
+    type Error = Value<'a>;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Array(items) => Ok(items.into_iter().collect()),
+            value => Err(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value<'_> {
+        Value::String(s.as_bytes().into())
+    }
+
+    #[test]
+    fn php_array_keys() {
+        let mut array = PhpArray::new();
+        assert!(array.is_list());
+        assert_eq!(array.push(string("a")), 0);
+        assert_eq!(array.insert("42", string("b")), None);
+        assert_eq!(array.insert("042", string("c")), None);
+        assert_eq!(array.insert(0, string("d")), Some(string("a")));
+        assert_eq!(array.push(string("e")), 43);
+        assert_eq!(array.insert(-7, string("f")), None);
+        assert_eq!(array.push(string("g")), 44);
+
+        assert_eq!(array.get(42), Some(&string("b")));
+        assert_eq!(array.get("0"), Some(&string("d")));
+        assert_eq!(array.get(b"042"), Some(&string("c")));
+        assert_eq!(array.get(String::from("-7")), Some(&string("f")));
+        assert!(!array.contains_key("-0"));
+        assert_eq!(
+            array.keys().cloned().collect::<Vec<_>>(),
+            [
+                ArrayKey::Integer(0),
+                ArrayKey::Integer(42),
+                ArrayKey::String(b"042".into()),
+                ArrayKey::Integer(43),
+                ArrayKey::Integer(-7),
+                ArrayKey::Integer(44),
+            ]
+        );
+
+        *array.get_mut(43).unwrap() = Value::Null;
+        assert_eq!(array.remove(42), Some(string("b")));
+        assert_eq!(array.remove(42), None);
+        assert_eq!(array.get(43), Some(&Value::Null));
+        assert_eq!(array.get(44), Some(&string("g")));
+        assert_eq!(array.len(), 5);
+        assert_eq!(array.push(string("h")), 45);
+
+        let value = crate::unserialize(b"a:1:{i:9223372036854775807;N;}").unwrap();
+        let mut full = PhpArray::try_from(value).unwrap();
+        assert_eq!(full.try_push(string("x")), Err(string("x")));
+        assert_eq!(full.len(), 1);
+        assert_eq!(full.try_push(Value::Null).ok(), None);
+    }
+
+    #[test]
+    fn php_array_lists() {
+        let list: PhpArray = [(0, string("a")), (1, string("b"))].into_iter().collect();
+        assert!(list.is_list());
+        let shuffled: PhpArray = [(1, string("a")), (0, string("b"))].into_iter().collect();
+        assert!(!shuffled.is_list());
+        assert_ne!(list, shuffled);
+
+        let mut holed = list.clone();
+        holed.remove(0);
+        assert!(!holed.is_list());
+    }
+
+    #[test]
+    fn php_array_values() {
+        let value = Value::Array(vec![
+            (ArrayKey::Integer(0), string("a")),
+            (ArrayKey::String(b"1".into()), string("b")),
+            (ArrayKey::Integer(0), string("c")),
+        ]);
+        let array = PhpArray::try_from(value).unwrap();
+        assert_eq!(
+            Value::from(array),
+            Value::Array(vec![
+                (ArrayKey::Integer(0), string("c")),
+                (ArrayKey::Integer(1), string("b")),
+            ])
+        );
+        assert_eq!(PhpArray::try_from(Value::Null), Err(Value::Null));
+    }
+}
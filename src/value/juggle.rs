@@ -0,0 +1,299 @@
+use std::borrow::Cow;
+
+use crate::ser::format_decimal;
+
+use super::Value;
+
+const TWO_POW_63: f64 = 9_223_372_036_854_775_808.0;
+const TWO_POW_64: f64 = 18_446_744_073_709_551_616.0;
+
+/// `precision` setting PHP uses by default when converting floats to strings.
+const PRECISION: u32 = 14;
+
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c')
+}
+
+/// Number at the start of a string as PHP reads it in casts: after whitespace, an integer, or a
+/// float with a fraction or an exponent, whatever follows being ignored.
+fn leading_number(bytes: &[u8]) -> Option<Number> {
+    let start = bytes
+        .iter()
+        .position(|byte| !is_whitespace(*byte))
+        .unwrap_or(bytes.len());
+    let bytes = &bytes[start..];
+    let digits = |from: usize| {
+        bytes
+            .iter()
+            .skip(from)
+            .take_while(|byte| byte.is_ascii_digit())
+            .count()
+    };
+
+    let mut end = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    let integer = digits(end);
+    end += integer;
+    let mut is_float = false;
+    if bytes.get(end) == Some(&b'.') {
+        let fraction = digits(end + 1);
+        if integer + fraction > 0 {
+            is_float = true;
+            end += 1 + fraction;
+        }
+    }
+    if integer == 0 && !is_float {
+        return None;
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+        let exponent = digits(end + 1 + sign);
+        if exponent > 0 {
+            is_float = true;
+            end += 1 + sign + exponent;
+        }
+    }
+
+    let number = std::str::from_utf8(&bytes[..end]).ok()?;
+    if !is_float {
+        // Integers out of `i64`'s range are read as floats.
+        if let Ok(n) = number.parse() {
+            return Some(Number::Integer(n));
+        }
+    }
+    number.parse().ok().map(Number::Float)
+}
+
+/// Float to integer as PHP's `zend_dval_to_lval`, wrapping around out of `i64`'s range.
+#[allow(clippy::cast_possible_truncation)]
+fn float_to_int(d: f64) -> i64 {
+    if !d.is_finite() {
+        return 0;
+    }
+    if (-TWO_POW_63..TWO_POW_63).contains(&d) {
+        return d as i64;
+    }
+    let mut modulo = d % TWO_POW_64;
+    if modulo < 0.0 {
+        modulo += TWO_POW_64;
+    }
+    if modulo >= TWO_POW_63 {
+        modulo -= TWO_POW_64;
+    }
+    modulo as i64
+}
+
+/// Float to integer as PHP's `zend_dval_to_lval_cap`, saturating out of `i64`'s range.
+#[allow(clippy::cast_possible_truncation)]
+fn float_to_int_capped(d: f64) -> i64 {
+    if d.is_finite() {
+        d as i64
+    } else {
+        0
+    }
+}
+
+/// Conversions following PHP 8's type juggling, as done by `(bool)`, `(int)`, `(float)`, and
+/// `(string)` casts.
+///
+/// They give `None` for [`Value::ValueReference`] and [`Value::ObjectReference`], which can't be
+/// resolved without the rest of the data.
+impl Value<'_> {
+    /// `false` for `null`, `false`, `0`, `0.0`, `""`, `"0"`, and empty arrays, `true` otherwise.
+    #[must_use]
+    pub fn to_php_bool(&self) -> Option<bool> {
+        Some(match self {
+            Value::Null => false,
+            Value::Boolean(b) => *b,
+            Value::Integer(n) => *n != 0,
+            Value::Decimal(d) => *d != 0.0,
+            Value::String(s) => !(s.is_empty() || **s == *b"0"),
+            Value::Array(items) => !items.is_empty(),
+            Value::Object { .. } | Value::CustomObject { .. } | Value::Enum { .. } => true,
+            Value::ValueReference(_) | Value::ObjectReference(_) => return None,
+        })
+    }
+
+    /// Integer value, strings being read up to their first non-numeric character, e.g. `"12abc"`
+    /// gives `12` and `"abc"` gives `0`.
+    ///
+    /// Floats out of `i64`'s range wrap around, while numeric strings out of range saturate.
+    #[must_use]
+    pub fn to_php_int(&self) -> Option<i64> {
+        Some(match self {
+            Value::Null => 0,
+            Value::Boolean(b) => i64::from(*b),
+            Value::Integer(n) => *n,
+            Value::Decimal(d) => float_to_int(*d),
+            Value::String(s) => match leading_number(s) {
+                Some(Number::Integer(n)) => n,
+                Some(Number::Float(d)) => float_to_int_capped(d),
+                None => 0,
+            },
+            Value::Array(items) => i64::from(!items.is_empty()),
+            // PHP warns, but gives 1.
+            Value::Object { .. } | Value::CustomObject { .. } | Value::Enum { .. } => 1,
+            Value::ValueReference(_) | Value::ObjectReference(_) => return None,
+        })
+    }
+
+    /// Float value, strings being read up to their first non-numeric character.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn to_php_float(&self) -> Option<f64> {
+        Some(match self {
+            Value::Decimal(d) => *d,
+            Value::String(s) => match leading_number(s) {
+                Some(Number::Integer(n)) => n as f64,
+                Some(Number::Float(d)) => d,
+                None => 0.0,
+            },
+            value => value.to_php_int()? as f64,
+        })
+    }
+
+    /// String value, floats being written with PHP's default `precision` of 14 digits, e.g.
+    /// `0.1 + 0.2` gives `"0.3"`.
+    ///
+    /// Arrays give `"Array"`, while objects give `None` as PHP can't convert them without a
+    /// `__toString()` method.
+    #[must_use]
+    pub fn to_php_string(&self) -> Option<Cow<'_, [u8]>> {
+        let string = match self {
+            Value::Null | Value::Boolean(false) => String::new(),
+            Value::Boolean(true) => "1".to_string(),
+            Value::Integer(n) => n.to_string(),
+            Value::Decimal(d) if d.is_nan() => "NAN".to_string(),
+            Value::Decimal(d) if d.is_infinite() => {
+                if d.is_sign_positive() { "INF" } else { "-INF" }.to_string()
+            }
+            Value::Decimal(d) => format_decimal(*d, Some(PRECISION)),
+            Value::String(s) => return Some(Cow::Borrowed(s)),
+            Value::Array(_) => "Array".to_string(),
+            Value::Object { .. }
+            | Value::CustomObject { .. }
+            | Value::Enum { .. }
+            | Value::ValueReference(_)
+            | Value::ObjectReference(_) => return None,
+        };
+        Some(Cow::Owned(string.into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use crate::value::ArrayKey;
+
+    use super::*;
+
+    fn string(s: &str) -> Value<'_> {
+        Value::String(s.as_bytes().into())
+    }
+
+    #[test]
+    fn juggle_to_bool() {
+        let cases = [
+            (Value::Null, false),
+            (Value::Integer(-1), true),
+            (Value::Decimal(-0.0), false),
+            (Value::Decimal(f64::NAN), true),
+            (string(""), false),
+            (string("0"), false),
+            (string("0.0"), true),
+            (string(" 0"), true),
+            (Value::Array(vec![]), false),
+            (
+                Value::Array(vec![(ArrayKey::Integer(0), Value::Null)]),
+                true,
+            ),
+            (
+                Value::Object {
+                    class_name: b"stdClass".into(),
+                    properties: vec![],
+                },
+                true,
+            ),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.to_php_bool(), Some(expected), "{value:?}");
+        }
+        let reference = Value::ValueReference(NonZeroUsize::MIN);
+        assert_eq!(reference.to_php_bool(), None);
+    }
+
+    #[test]
+    fn juggle_to_int() {
+        let cases = [
+            (Value::Boolean(true), 1),
+            (Value::Decimal(-3.9), -3),
+            (Value::Decimal(1e20), 7_766_279_631_452_241_920),
+            (Value::Decimal(f64::INFINITY), 0),
+            (string("42"), 42),
+            (string(" \n-12abc"), -12),
+            (string("+7"), 7),
+            (string("1e3"), 1000),
+            (string("1.9e"), 1),
+            (string(".5"), 0),
+            (string("0x1A"), 0),
+            (string("abc"), 0),
+            (string("-"), 0),
+            (string("99999999999999999999"), i64::MAX),
+            (string("-1e100"), i64::MIN),
+            (Value::Array(vec![(ArrayKey::Integer(0), Value::Null)]), 1),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.to_php_int(), Some(expected), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn juggle_to_float() {
+        let cases = [
+            (Value::Null, 0.0),
+            (Value::Integer(3), 3.0),
+            (string("1.5kg"), 1.5),
+            (string("  -.5e1"), -5.0),
+            (string("1."), 1.0),
+            (string("99999999999999999999"), 1e20),
+            (string("."), 0.0),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.to_php_float(), Some(expected), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn juggle_to_string() {
+        let cases: [(Value, &[u8]); 11] = [
+            (Value::Null, b""),
+            (Value::Boolean(false), b""),
+            (Value::Boolean(true), b"1"),
+            (Value::Integer(-42), b"-42"),
+            (Value::Decimal(0.1 + 0.2), b"0.3"),
+            (Value::Decimal(1.0), b"1"),
+            (Value::Decimal(-0.0), b"-0"),
+            (Value::Decimal(1e15), b"1.0E+15"),
+            (Value::Decimal(f64::NEG_INFINITY), b"-INF"),
+            (string("abc"), b"abc"),
+            (Value::Array(vec![]), b"Array"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                value.to_php_string().as_deref(),
+                Some(expected),
+                "{value:?}"
+            );
+        }
+        let value = Value::Enum {
+            class_name: b"Suit".into(),
+            case: b"Hearts".into(),
+        };
+        assert_eq!(value.to_php_string(), None);
+    }
+}
@@ -1,6 +1,37 @@
-use muesli::{session_decode, unserialize};
+use muesli::{
+    session_decode, session_decode_with, unserialize, unserialize_with, validate, validate_session,
+    DecodeOptions, Error, EventReader, Graph, LazySession, LimitExceeded,
+};
 use proptest::prelude::*;
 
+fn nested_arrays(depth: usize) -> Vec<u8> {
+    let mut data = b"a:1:{i:0;".repeat(depth);
+    data.extend_from_slice(b"N;");
+    data.extend(std::iter::repeat(b'}').take(depth));
+    data
+}
+
+fn nested_objects(depth: usize) -> Vec<u8> {
+    let mut data = b"O:8:\"stdClass\":1:{s:1:\"x\";".repeat(depth);
+    data.extend_from_slice(b"N;");
+    data.extend(std::iter::repeat(b'}').take(depth));
+    data
+}
+
+fn limit(err: &Error) -> Option<LimitExceeded> {
+    match err {
+        Error::LimitExceeded { limit, .. } => Some(*limit),
+        _ => None,
+    }
+}
+
+fn depth_limit(err: &Error) -> Option<usize> {
+    match err {
+        Error::DepthExceeded { max, .. } => Some(*max),
+        _ => None,
+    }
+}
+
 proptest! {
     #![proptest_config(ProptestConfig::with_cases(10_000))]
 
@@ -13,4 +44,212 @@ proptest! {
     fn session_decode_should_not_panic(data: Vec<u8>) {
         let _ = session_decode(&data);
     }
+
+    #[test]
+    fn validate_should_agree_with_decoding(data: Vec<u8>) {
+        prop_assert_eq!(validate(&data).is_ok(), unserialize(&data).is_ok());
+        prop_assert_eq!(validate_session(&data).is_ok(), session_decode(&data).is_ok());
+    }
+
+    #[test]
+    fn lazy_session_should_agree_with_session_decode(data: Vec<u8>) {
+        let entries = session_decode(&data);
+        let session = LazySession::new(&data);
+        prop_assert_eq!(session.is_ok(), entries.is_ok());
+        if let (Ok(session), Ok(entries)) = (session, entries) {
+            prop_assert_eq!(session.len(), entries.len());
+            for (lazy, entry) in session.entries().iter().zip(&entries) {
+                prop_assert_eq!(&lazy.key, &entry.key);
+                prop_assert_eq!(session.decode(lazy).ok(), Some(entry.value.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn event_reader_should_not_panic(data: Vec<u8>) {
+        let _ = EventReader::new(data.as_slice()).count();
+    }
+
+    #[test]
+    fn event_reader_should_accept_unserialize_input(data: Vec<u8>) {
+        if unserialize(&data).is_ok() {
+            let events: Result<Vec<_>, _> = EventReader::new(data.as_slice()).collect();
+            prop_assert!(events.is_ok());
+        }
+    }
+
+    #[test]
+    fn reference_resolution_should_not_panic(indexes: Vec<(bool, u8)>) {
+        let mut data = format!("a:{}:{{", indexes.len()).into_bytes();
+        for (i, (object, index)) in indexes.iter().enumerate() {
+            let value = match (object, index) {
+                (_, 0) => "O:8:\"stdClass\":0:{}".to_string(),
+                (true, index) => format!("r:{index};"),
+                (false, index) => format!("R:{index};"),
+            };
+            data.extend_from_slice(format!("i:{i};{value}").as_bytes());
+        }
+        data.push(b'}');
+        if let Ok(value) = unserialize(&data) {
+            let _ = Graph::from_value(value);
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(100))]
+
+    #[test]
+    fn nesting_should_respect_max_depth(depth in 1..64usize, max_depth in 1..64usize) {
+        let options = DecodeOptions::new().with_max_depth(max_depth);
+        for data in [nested_arrays(depth), nested_objects(depth)] {
+            let output = unserialize_with(&data, &options);
+            if depth <= max_depth {
+                prop_assert!(output.is_ok());
+            } else {
+                prop_assert_eq!(depth_limit(&output.unwrap_err()), Some(max_depth));
+            }
+        }
+    }
+}
+
+#[test]
+fn default_max_depth_is_reachable() {
+    assert!(unserialize(&nested_arrays(128)).is_ok());
+    assert!(unserialize(&nested_objects(128)).is_ok());
+    assert!(unserialize(&nested_arrays(129)).is_err());
+}
+
+#[test]
+fn hostile_nesting_should_not_overflow_stack() {
+    for data in [nested_arrays(1_000_000), nested_objects(1_000_000)] {
+        assert_eq!(depth_limit(&unserialize(&data).unwrap_err()), Some(128));
+
+        let mut session = b"foo|i:1;bar|".to_vec();
+        session.extend_from_slice(&data);
+        assert_eq!(
+            depth_limit(&session_decode(&session).unwrap_err()),
+            Some(128)
+        );
+
+        let options = DecodeOptions::new().with_max_depth(10);
+        assert_eq!(
+            depth_limit(&session_decode_with(&session, &options).unwrap_err()),
+            Some(10)
+        );
+    }
+}
+
+#[test]
+fn event_reader_should_not_recurse() {
+    let depth = 100_000;
+    let options = DecodeOptions::new().with_max_depth(depth);
+    for data in [nested_arrays(depth), nested_objects(depth)] {
+        let events = EventReader::with_options(data.as_slice(), &options).count();
+        // Start, key and end per level, around the innermost `N;`.
+        assert_eq!(events, depth * 3 + 1);
+    }
+}
+
+#[test]
+fn hostile_sizes_should_be_rejected_before_allocating() {
+    let cases: [(&[u8], LimitExceeded); 6] = [
+        (
+            b"a:999999999999:{}",
+            LimitExceeded::RemainingInput {
+                declared: 999_999_999_999,
+                remaining: 3,
+            },
+        ),
+        (
+            b"O:8:\"stdClass\":999999999999:{}",
+            LimitExceeded::RemainingInput {
+                declared: 999_999_999_999,
+                remaining: 3,
+            },
+        ),
+        (
+            b"a:2:{i:0;N;}",
+            LimitExceeded::RemainingInput {
+                declared: 2,
+                remaining: 9,
+            },
+        ),
+        (
+            b"s:99999999999999999:\"\";",
+            LimitExceeded::RemainingInput {
+                declared: 99_999_999_999_999_999,
+                remaining: 4,
+            },
+        ),
+        (
+            b"a:1:{S:999999999999:\"\";N;}",
+            LimitExceeded::RemainingInput {
+                declared: 999_999_999_999,
+                remaining: 7,
+            },
+        ),
+        (
+            b"C:3:\"Foo\":999999999999:{}",
+            LimitExceeded::RemainingInput {
+                declared: 999_999_999_999,
+                remaining: 3,
+            },
+        ),
+    ];
+    for (data, expected) in cases {
+        assert_eq!(limit(&unserialize(data).unwrap_err()), Some(expected));
+    }
+}
+
+#[test]
+fn sizes_should_respect_limits() {
+    let data = b"a:2:{i:0;s:3:\"foo\";i:1;a:1:{i:0;N;}}";
+    assert!(unserialize(data).is_ok());
+
+    let options = DecodeOptions::new().with_max_container_len(1);
+    let err = unserialize_with(data, &options).unwrap_err();
+    assert_eq!(err.offset(), Some(2));
+    assert_eq!(
+        limit(&err),
+        Some(LimitExceeded::ContainerLength {
+            declared: 2,
+            max: 1
+        })
+    );
+
+    let options = DecodeOptions::new().with_max_total_elements(2);
+    let err = unserialize_with(data, &options).unwrap_err();
+    assert_eq!(err.offset(), Some(25));
+    assert_eq!(limit(&err), Some(LimitExceeded::TotalElements { max: 2 }));
+
+    let options = DecodeOptions::new().with_max_string_len(2);
+    let err = unserialize_with(data, &options).unwrap_err();
+    assert_eq!(err.offset(), Some(11));
+    assert_eq!(
+        limit(&err),
+        Some(LimitExceeded::StringLength {
+            declared: 3,
+            max: 2
+        })
+    );
+
+    let data = b"C:3:\"Foo\":5:{abcde}";
+    let options = DecodeOptions::new().with_max_string_len(3);
+    let err = unserialize_with(data, &options).unwrap_err();
+    assert_eq!(err.offset(), Some(10));
+    let expected = LimitExceeded::StringLength {
+        declared: 5,
+        max: 3,
+    };
+    assert_eq!(limit(&err), Some(expected));
+    let events: Result<Vec<_>, _> = EventReader::with_options(data.as_slice(), &options).collect();
+    assert_eq!(limit(&events.unwrap_err()), Some(expected));
+
+    let session = b"a|a:1:{i:0;N;}b|a:1:{i:0;N;}";
+    let options = DecodeOptions::new().with_max_total_elements(1);
+    assert_eq!(
+        limit(&session_decode_with(session, &options).unwrap_err()),
+        Some(LimitExceeded::TotalElements { max: 1 })
+    );
 }
@@ -1,4 +1,7 @@
-use muesli::{serialize, session_decode, session_encode, unserialize};
+use muesli::{
+    serialize, session_decode, session_decode_with, session_encode, session_encode_with,
+    unserialize, DecodeOptions, EncodeOptions, SessionEntry, SessionFormat, Value,
+};
 
 #[test]
 fn base_serialize_case() {
@@ -10,6 +13,27 @@ fn base_serialize_case() {
     assert_eq!(buffer, data);
 }
 
+#[test]
+fn float_case() {
+    let data = b"a:6:{i:0;d:1.0E+25;i:1;d:-1.5E-9;i:2;d:-0;i:3;d:0.1;i:4;d:0.30000000000000004;i:5;d:1.7976931348623157E+308;}".as_slice();
+
+    let mut buffer = Vec::new();
+    serialize(&mut buffer, &unserialize(data).unwrap()).unwrap();
+
+    assert_eq!(buffer, data);
+}
+
+#[test]
+fn inherited_private_property_case() {
+    let data = b"O:5:\"Child\":2:{s:12:\"\0Parent\0name\";s:3:\"foo\";s:11:\"\0Child\0name\";s:3:\"bar\";}"
+        .as_slice();
+
+    let mut buffer = Vec::new();
+    serialize(&mut buffer, &unserialize(data).unwrap()).unwrap();
+
+    assert_eq!(buffer, data);
+}
+
 #[test]
 fn base_session_case() {
     let data = br#"foo|a:4:{i:0;i:1;i:1;i:2;i:2;i:3;i:3;i:4;}bar|s:22:"complicated string "|!";baz|d:NAN;qux|b:1;"#.as_slice();
@@ -29,3 +53,107 @@ fn large_session_case() {
 
     assert_eq!(buffer, data);
 }
+
+#[test]
+fn owned_session_case() {
+    let data = br#"foo|a:2:{i:0;s:3:"bar";s:3:"baz";O:8:"stdClass":1:{s:3:"qux";d:0.5;}}pub|C:3:"Foo":3:{bar}"#.to_vec();
+
+    let session: Vec<SessionEntry<'static>> = session_decode(&data)
+        .unwrap()
+        .into_iter()
+        .map(SessionEntry::into_owned)
+        .collect();
+    let expected = data.clone();
+    drop(data);
+
+    let encoded = std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        session_encode(&mut buffer, &session).unwrap();
+        buffer
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(encoded, expected);
+}
+
+#[test]
+fn borrowed_value_case() {
+    let value = Value::String(b"computed".to_vec().into()).into_owned();
+
+    let mut owned = Vec::new();
+    serialize(&mut owned, &value).unwrap();
+    let mut borrowed = Vec::new();
+    serialize(&mut borrowed, &value.as_borrowed()).unwrap();
+
+    assert_eq!(owned, borrowed);
+    assert_eq!(unserialize(&owned).unwrap(), value);
+}
+
+#[test]
+fn enum_session_case() {
+    let data = br#"status|E:17:"App\Status:Active";roles|a:2:{i:0;E:15:"App\Role:Editor";i:1;E:14:"App\Role:Admin";}"#.as_slice();
+
+    let mut buffer = Vec::new();
+    session_encode(&mut buffer, &session_decode(data).unwrap()).unwrap();
+
+    assert_eq!(buffer, data);
+}
+
+#[test]
+fn escaped_session_case() {
+    let data = br#"foo|S:4:"a\00b\5c";bar|a:1:{S:3:"\09ok";S:0:"";}"#.as_slice();
+
+    let options = EncodeOptions::new().with_escaped_strings(true);
+    let mut buffer = Vec::new();
+    session_encode_with(&mut buffer, &session_decode(data).unwrap(), &options).unwrap();
+
+    assert_eq!(buffer, data);
+}
+
+#[test]
+fn php_serialize_session_case() {
+    let data = br#"a:3:{s:3:"foo";a:2:{i:0;i:1;i:1;i:2;}s:3:"bar";s:22:"complicated string "|!";s:4:"baz!";d:NAN;}"#.as_slice();
+
+    let decode_options = DecodeOptions::new().with_session_format(SessionFormat::PhpSerialize);
+    let encode_options = EncodeOptions::new().with_session_format(SessionFormat::PhpSerialize);
+    let session = session_decode_with(data, &decode_options).unwrap();
+    let mut buffer = Vec::new();
+    session_encode_with(&mut buffer, &session, &encode_options).unwrap();
+
+    assert_eq!(buffer, data);
+
+    let mut php = Vec::new();
+    session_encode(&mut php, &session[..2]).unwrap();
+    assert_eq!(
+        php,
+        br#"foo|a:2:{i:0;i:1;i:1;i:2;}bar|s:22:"complicated string "|!";"#
+    );
+}
+
+#[test]
+fn php_binary_session_case() {
+    let data = b"\x03fooa:2:{i:0;i:1;i:1;i:2;}\x03bars:22:\"complicated string \"|!\";\x84gone\x04baz!d:NAN;".as_slice();
+
+    let decode_options = DecodeOptions::new().with_session_format(SessionFormat::PhpBinary);
+    let encode_options = EncodeOptions::new().with_session_format(SessionFormat::PhpBinary);
+    let session = session_decode_with(data, &decode_options).unwrap();
+    let mut buffer = Vec::new();
+    session_encode_with(&mut buffer, &session, &encode_options).unwrap();
+
+    assert_eq!(buffer, data);
+    assert_eq!(session[2].value, None);
+}
+
+#[test]
+fn undefined_session_case() {
+    let data = b"!foo|bar|a:1:{i:0;b:1;}!baz|qux|N;".as_slice();
+
+    let session = session_decode(data).unwrap();
+    let mut buffer = Vec::new();
+    session_encode(&mut buffer, &session).unwrap();
+
+    assert_eq!(buffer, data);
+    assert_eq!(session[0].value, None);
+    assert_eq!(session[2].value, None);
+}